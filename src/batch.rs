@@ -0,0 +1,175 @@
+use std::time::{Duration, Instant};
+
+use crate::{Engine, engine::parse_givens};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The solved grid as 81 digits.
+    Solved(String),
+    Unsatisfiable,
+    TimedOut,
+    ParseError(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PuzzleResult {
+    pub outcome: Outcome,
+    pub branches: u32,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BatchReport {
+    /// One entry per puzzle, in input order.
+    pub results: Vec<PuzzleResult>,
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    pub fn solved(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Solved(_)))
+    }
+
+    pub fn unsatisfiable(&self) -> usize {
+        self.count(|o| *o == Outcome::Unsatisfiable)
+    }
+
+    pub fn timed_out(&self) -> usize {
+        self.count(|o| *o == Outcome::TimedOut)
+    }
+
+    pub fn parse_errors(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::ParseError(_)))
+    }
+
+    pub fn branches(&self) -> u64 {
+        self.results.iter().map(|r| r.branches as u64).sum()
+    }
+
+    fn count(&self, f: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|r| f(&r.outcome)).count()
+    }
+}
+
+/// Solve every puzzle against the constraints already posted on `template`.
+/// The template is reset between puzzles, so it is built once and anything
+/// learned along the way is dropped; it is left as it was passed in.
+/// `time_limit` applies to each puzzle on its own.
+pub fn solve_batch<S: AsRef<str>>(
+    template: &mut Engine,
+    puzzles: &[S],
+    time_limit: Option<Duration>,
+) -> BatchReport {
+    let start = Instant::now();
+    let state = template.state.clone();
+    let constraints = template.constraints.len();
+
+    let mut results = Vec::with_capacity(puzzles.len());
+    for p in puzzles {
+        let t0 = Instant::now();
+        let b0 = template.branches();
+        let outcome = solve_one(template, p.as_ref(), time_limit.map(|d| t0 + d));
+        results.push(PuzzleResult {
+            outcome,
+            branches: template.branches() - b0,
+            elapsed: t0.elapsed(),
+        });
+        template.reset(&state, constraints);
+    }
+
+    BatchReport {
+        results,
+        elapsed: start.elapsed(),
+    }
+}
+
+fn solve_one(eng: &mut Engine, givens: &str, deadline: Option<Instant>) -> Outcome {
+    if let Err(e) = parse_givens(givens) {
+        return Outcome::ParseError(e);
+    }
+    if eng.load_givens(givens).is_err() {
+        return Outcome::Unsatisfiable;
+    }
+    let res = match deadline {
+        Some(t) => eng.search_until(t),
+        None => eng.search().map(Some),
+    };
+    match res {
+        Ok(Some(true)) => Outcome::Solved(
+            eng.state
+                .domains
+                .iter()
+                .map(|&m| char::from(b'0' + m.trailing_zeros() as u8))
+                .collect(),
+        ),
+        Ok(Some(false)) | Err(_) => Outcome::Unsatisfiable,
+        Ok(None) => Outcome::TimedOut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_all_sudoku_constraints;
+    use crate::tests::{HARD, HARD_UNSAT};
+
+    #[test]
+    fn reports_each_outcome() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        let puzzles = [
+            HARD,
+            HARD_UNSAT,
+            // two 8s in row 1
+            "88.........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..",
+            "too short",
+        ];
+        let report = solve_batch(&mut eng, &puzzles, None);
+
+        match &report.results[0].outcome {
+            Outcome::Solved(grid) => assert!(grid.starts_with("812753649")),
+            o => panic!("unexpected {o:?}"),
+        }
+        assert_eq!(report.results[1].outcome, Outcome::Unsatisfiable);
+        assert_eq!(report.results[2].outcome, Outcome::Unsatisfiable);
+        assert!(matches!(report.results[3].outcome, Outcome::ParseError(_)));
+        assert_eq!(
+            (
+                report.solved(),
+                report.unsatisfiable(),
+                report.parse_errors()
+            ),
+            (1, 2, 1)
+        );
+        assert!(report.results[1].branches > 0);
+    }
+
+    #[test]
+    fn template_is_reused_unchanged() {
+        let mut eng = Engine::new();
+        eng.learning = true;
+        add_all_sudoku_constraints(&mut eng);
+        let n = eng.constraints.len();
+        assert_eq!(eng.nogoods(), 0);
+
+        let report = solve_batch(&mut eng, &[HARD, HARD], None);
+        let (a, b) = (&report.results[0], &report.results[1]);
+        assert_eq!((&a.outcome, a.branches), (&b.outcome, b.branches));
+        assert_eq!(eng.constraints.len(), n);
+        assert_eq!(eng.nogoods(), 0);
+        assert!(eng.state.domains.iter().all(|&m| m == crate::DIGITS_MASK));
+    }
+
+    #[test]
+    fn times_out() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        let report = solve_batch(&mut eng, &[HARD, HARD], Some(Duration::ZERO));
+        assert_eq!(report.results[0].outcome, Outcome::TimedOut);
+        assert_eq!(report.timed_out(), 2);
+
+        // the timeout does not leak into the next search
+        eng.load_givens(HARD).unwrap();
+        assert!(eng.search().unwrap());
+    }
+}
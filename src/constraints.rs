@@ -1,30 +1,335 @@
-use crate::{CellIx, Contradiction, DIGITS_MASK, Domain, EVEN_MASK, State};
+use crate::{
+    CellIx, Contradiction, DIGITS_MASK, Domain, EVEN_MASK, N, State,
+    types::{
+        HIGH_MASK, LOW_MASK, MID_MASK, box_of, col_of, idx, max_digit, min_digit, range_mask,
+        row_of,
+    },
+};
+
+/// A rule the engine can propagate. The built-in [`Constraint`] variants go
+/// through this trait too; implement it to add a rule without touching the
+/// enum and post it with `Box::new(..)` through `Engine::add_constraint`.
+pub trait Propagator: Send + Sync {
+    /// Cells whose changes should wake this propagator.
+    fn scope<'a>(&'a self) -> Box<dyn Iterator<Item = CellIx> + 'a>;
+
+    /// Narrow `state` and report whether any domain changed.
+    fn propagate(&self, state: &mut State) -> Result<bool, Contradiction>;
+
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Propagators with a nonzero priority jump ahead of the queue. Meant for
+    /// cheap rules that prune before the expensive ones run.
+    fn priority(&self) -> u8 {
+        0
+    }
+}
+
+/// How hard `AllDifferent` constraints filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AllDiffLevel {
+    /// Singles and hidden singles.
+    #[default]
+    Basic,
+    /// Also every naked and hidden subset, via bipartite matching.
+    Matching,
+}
 
 pub enum Constraint {
-    AllDifferent { cells: [CellIx; 9] },
-    KropkiWhite { a: CellIx, b: CellIx },
-    KropkiBlack { a: CellIx, b: CellIx },
-    Thermo { cells: Vec<CellIx> },
+    AllDifferent {
+        cells: [CellIx; 9],
+        level: AllDiffLevel,
+    },
+    KropkiWhite {
+        a: CellIx,
+        b: CellIx,
+    },
+    KropkiBlack {
+        a: CellIx,
+        b: CellIx,
+    },
+    Thermo {
+        cells: Vec<CellIx>,
+    },
+    LittleKiller {
+        cells: Vec<CellIx>,
+        sum: u8,
+    },
+    /// No knight's move away from `cell` repeats its digit.
+    AntiKnight {
+        cell: CellIx,
+    },
+    AntiKing {
+        cell: CellIx,
+    },
+    /// No orthogonal neighbour of `cell` holds a digit next to its own.
+    NonConsecutive {
+        cell: CellIx,
+    },
+    Less {
+        a: CellIx,
+        b: CellIx,
+    },
+    Quadruple {
+        cells: [CellIx; 4],
+        digits: Vec<u8>,
+    },
+    Palindrome {
+        cells: Vec<CellIx>,
+    },
+    Clone {
+        a: Vec<CellIx>,
+        b: Vec<CellIx>,
+    },
+    Between {
+        end_a: CellIx,
+        end_b: CellIx,
+        middle: Vec<CellIx>,
+    },
+    Lockout {
+        end_a: CellIx,
+        end_b: CellIx,
+        middle: Vec<CellIx>,
+        min_diff: u8,
+    },
+    RegionSum {
+        cells: Vec<CellIx>,
+    },
+    Entropic {
+        cells: Vec<CellIx>,
+    },
+    Modular {
+        cells: Vec<CellIx>,
+    },
+    Zipper {
+        cells: Vec<CellIx>,
+    },
+    XSum {
+        cells: [CellIx; 9],
+        sum: u8,
+    },
+    Skyscraper {
+        cells: [CellIx; 9],
+        visible: u8,
+    },
+    Index {
+        index_cell: CellIx,
+        house: [CellIx; 9],
+        digit: u8,
+    },
+    Table {
+        cells: Vec<CellIx>,
+        tuples: Vec<Vec<u8>>,
+    },
+    Intersection {
+        a: [CellIx; 9],
+        b: [CellIx; 9],
+    },
+    Nogood {
+        lits: Vec<(CellIx, u8)>,
+    },
+    Custom(Box<dyn Propagator>),
+}
+
+impl From<Box<dyn Propagator>> for Constraint {
+    fn from(p: Box<dyn Propagator>) -> Self {
+        Constraint::Custom(p)
+    }
+}
+
+impl<P: Propagator + 'static> From<Box<P>> for Constraint {
+    fn from(p: Box<P>) -> Self {
+        Constraint::Custom(p)
+    }
+}
+
+const KNIGHT_MOVES: [(isize, isize); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+
+const KING_MOVES: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+const ENTROPY_CLASSES: [Domain; 3] = [LOW_MASK, MID_MASK, HIGH_MASK];
+
+const MOD3_CLASSES: [Domain; 3] = [
+    (1 << 3) | (1 << 6) | (1 << 9),
+    (1 << 1) | (1 << 4) | (1 << 7),
+    (1 << 2) | (1 << 5) | (1 << 8),
+];
+
+const ORTHOGONAL_MOVES: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+/// Cell a `(dr, dc)` step away from `i`, if it is still on the grid.
+fn step(i: CellIx, (dr, dc): (isize, isize)) -> Option<CellIx> {
+    let r = row_of(i) as isize + dr;
+    let c = col_of(i) as isize + dc;
+    if (0..N as isize).contains(&r) && (0..N as isize).contains(&c) {
+        Some(idx(r as usize, c as usize))
+    } else {
+        None
+    }
 }
 
-impl Constraint {
-    pub fn scope<'a>(&'a self) -> Box<dyn Iterator<Item = CellIx> + 'a> {
+impl Propagator for Constraint {
+    fn scope<'a>(&'a self) -> Box<dyn Iterator<Item = CellIx> + 'a> {
         match self {
-            Constraint::AllDifferent { cells } => Box::new(cells.iter().copied()),
+            Constraint::AllDifferent { cells, .. }
+            | Constraint::XSum { cells, .. }
+            | Constraint::Skyscraper { cells, .. } => Box::new(cells.iter().copied()),
+            // the index cell is always one of the house cells
+            Constraint::Index { house, .. } => Box::new(house.iter().copied()),
             Constraint::KropkiWhite { a, b } => Box::new([*a, *b].into_iter()),
             Constraint::KropkiBlack { a, b } => Box::new([*a, *b].into_iter()),
             Constraint::Thermo { cells } => Box::new(cells.iter().copied()),
+            Constraint::LittleKiller { cells, .. } => Box::new(cells.iter().copied()),
+            Constraint::RegionSum { cells } => Box::new(cells.iter().copied()),
+            Constraint::Entropic { cells }
+            | Constraint::Modular { cells }
+            | Constraint::Zipper { cells } => Box::new(cells.iter().copied()),
+            Constraint::AntiKnight { cell }
+            | Constraint::AntiKing { cell }
+            | Constraint::NonConsecutive { cell } => Box::new(std::iter::once(*cell)),
+            Constraint::Less { a, b } => Box::new([*a, *b].into_iter()),
+            Constraint::Quadruple { cells, .. } => Box::new(cells.iter().copied()),
+            Constraint::Palindrome { cells } => Box::new(cells.iter().copied()),
+            Constraint::Clone { a, b } => Box::new(a.iter().chain(b.iter()).copied()),
+            Constraint::Between {
+                end_a,
+                end_b,
+                middle,
+            }
+            | Constraint::Lockout {
+                end_a,
+                end_b,
+                middle,
+                ..
+            } => Box::new([*end_a, *end_b].into_iter().chain(middle.iter().copied())),
+            Constraint::Table { cells, .. } => Box::new(cells.iter().copied()),
+            Constraint::Intersection { a, b } => {
+                Box::new(a.iter().chain(b.iter().filter(|i| !a.contains(i))).copied())
+            }
+            Constraint::Nogood { lits } => Box::new(lits.iter().map(|&(i, _)| i)),
+            Constraint::Custom(p) => p.scope(),
         }
     }
 
-    pub fn propagate(&self, state: &mut State) -> Result<bool, Contradiction> {
+    fn propagate(&self, state: &mut State) -> Result<bool, Contradiction> {
         match self {
-            Constraint::AllDifferent { cells } => propagate_all_diff(state, cells),
+            Constraint::AllDifferent { cells, level } => match level {
+                AllDiffLevel::Basic => propagate_all_diff(state, cells),
+                AllDiffLevel::Matching => propagate_all_diff_matching(state, cells),
+            },
             Constraint::KropkiWhite { a, b } => propagate_kropki_white(state, *a, *b),
             Constraint::KropkiBlack { a, b } => propagate_kropki_black(state, *a, *b),
-            _ => {
-                todo!()
+            Constraint::LittleKiller { cells, sum } => propagate_sum(state, cells, *sum),
+            Constraint::RegionSum { cells } => propagate_region_sum(state, cells),
+            Constraint::Entropic { cells } => propagate_class_line(state, cells, &ENTROPY_CLASSES),
+            Constraint::Modular { cells } => propagate_class_line(state, cells, &MOD3_CLASSES),
+            Constraint::Zipper { cells } => propagate_zipper(state, cells),
+            Constraint::XSum { cells, sum } => propagate_x_sum(state, cells, *sum),
+            Constraint::Skyscraper { cells, visible } => {
+                propagate_skyscraper(state, cells, *visible)
+            }
+            Constraint::Index {
+                index_cell,
+                house,
+                digit,
+            } => propagate_index(state, *index_cell, house, *digit),
+            Constraint::AntiKnight { cell } => propagate_anti_move(state, *cell, &KNIGHT_MOVES),
+            Constraint::AntiKing { cell } => propagate_anti_move(state, *cell, &KING_MOVES),
+            Constraint::NonConsecutive { cell } => propagate_non_consecutive(state, *cell),
+            Constraint::Less { a, b } => propagate_less(state, *a, *b),
+            Constraint::Thermo { cells } => propagate_thermo(state, cells),
+            Constraint::Quadruple { cells, digits } => propagate_quadruple(state, cells, digits),
+            Constraint::Palindrome { cells } => {
+                let n = cells.len();
+                propagate_equal_pairs(state, (0..n / 2).map(|k| (cells[k], cells[n - 1 - k])))
+            }
+            Constraint::Clone { a, b } => {
+                // zip would drop the unpaired cells of the longer region
+                if a.len() != b.len() {
+                    return Err(Contradiction);
+                }
+                propagate_equal_pairs(state, a.iter().copied().zip(b.iter().copied()))
             }
+            Constraint::Between {
+                end_a,
+                end_b,
+                middle,
+            } => propagate_between(state, *end_a, *end_b, middle),
+            Constraint::Lockout {
+                end_a,
+                end_b,
+                middle,
+                min_diff,
+            } => propagate_lockout(state, *end_a, *end_b, middle, *min_diff),
+            Constraint::Table { cells, tuples } => propagate_table(state, cells, tuples),
+            Constraint::Intersection { a, b } => {
+                let ab = propagate_intersection(state, a, b)?;
+                let ba = propagate_intersection(state, b, a)?;
+                Ok(ab || ba)
+            }
+            Constraint::Nogood { lits } => propagate_nogood(state, lits),
+            Constraint::Custom(p) => p.propagate(state),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Constraint::AllDifferent { .. } => "all-different",
+            Constraint::KropkiWhite { .. } => "kropki-white",
+            Constraint::KropkiBlack { .. } => "kropki-black",
+            Constraint::Thermo { .. } => "thermo",
+            Constraint::LittleKiller { .. } => "little-killer",
+            Constraint::AntiKnight { .. } => "anti-knight",
+            Constraint::AntiKing { .. } => "anti-king",
+            Constraint::NonConsecutive { .. } => "non-consecutive",
+            Constraint::Less { .. } => "less",
+            Constraint::Quadruple { .. } => "quadruple",
+            Constraint::Palindrome { .. } => "palindrome",
+            Constraint::Clone { .. } => "clone",
+            Constraint::Between { .. } => "between",
+            Constraint::Lockout { .. } => "lockout",
+            Constraint::RegionSum { .. } => "region-sum",
+            Constraint::Entropic { .. } => "entropic",
+            Constraint::Modular { .. } => "modular",
+            Constraint::Zipper { .. } => "zipper",
+            Constraint::XSum { .. } => "x-sum",
+            Constraint::Skyscraper { .. } => "skyscraper",
+            Constraint::Index { .. } => "index",
+            Constraint::Table { .. } => "table",
+            Constraint::Intersection { .. } => "intersection",
+            Constraint::Nogood { .. } => "nogood",
+            Constraint::Custom(p) => p.name(),
+        }
+    }
+
+    fn priority(&self) -> u8 {
+        match self {
+            Constraint::KropkiWhite { .. }
+            | Constraint::KropkiBlack { .. }
+            | Constraint::Less { .. }
+            | Constraint::Nogood { .. } => 1,
+            Constraint::Custom(p) => p.priority(),
+            _ => 0,
         }
     }
 }
@@ -96,6 +401,196 @@ fn propagate_all_diff(st: &mut State, cells: &[CellIx; 9]) -> Result<bool, Contr
     Ok(changed)
 }
 
+/// All-different with matching-based filtering (Régin): find a perfect
+/// matching of cells to digits, then keep a cell/digit edge only if it is
+/// matched or lies on an alternating cycle. This removes every naked and
+/// hidden subset in one pass.
+pub(crate) fn propagate_all_diff_matching(
+    st: &mut State,
+    cells: &[CellIx; 9],
+) -> Result<bool, Contradiction> {
+    let mut changed = propagate_all_diff(st, cells)?;
+
+    let doms: [Domain; 9] = cells.map(|i| st.domains[i as usize]);
+    // digit_of[c] = matched digit of cell c, cell_of[d] = matched cell of digit d
+    let mut digit_of = [0usize; 9];
+    let mut cell_of = [usize::MAX; 10];
+    for c in 0..9 {
+        let mut seen: Domain = 0;
+        if !augment(c, &doms, &mut digit_of, &mut cell_of, &mut seen) {
+            return Err(Contradiction);
+        }
+    }
+
+    // nodes 0..9 are cells, 9..18 digits 1..=9; unmatched edges run cell ->
+    // digit, matched edges digit -> cell
+    let mut adj: [Vec<usize>; 18] = Default::default();
+    for c in 0..9 {
+        let mut m = doms[c];
+        while m != 0 {
+            let d = m.trailing_zeros() as usize;
+            m &= !(1u16 << d);
+            if digit_of[c] != d {
+                adj[c].push(8 + d);
+            }
+        }
+        adj[8 + digit_of[c]].push(c);
+    }
+    let scc = strongly_connected(&adj);
+
+    for (c, &i) in cells.iter().enumerate() {
+        let mut keep: Domain = 1u16 << digit_of[c];
+        let mut m = doms[c];
+        while m != 0 {
+            let d = m.trailing_zeros() as usize;
+            m &= !(1u16 << d);
+            if scc[c] == scc[8 + d] {
+                keep |= 1u16 << d;
+            }
+        }
+        if st.narrow(i, keep)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Kuhn's augmenting path from cell `c`.
+fn augment(
+    c: usize,
+    doms: &[Domain; 9],
+    digit_of: &mut [usize; 9],
+    cell_of: &mut [usize; 10],
+    seen: &mut Domain,
+) -> bool {
+    let mut m = doms[c] & !*seen;
+    while m != 0 {
+        let d = m.trailing_zeros() as usize;
+        m &= !(1u16 << d);
+        *seen |= 1u16 << d;
+        if cell_of[d] == usize::MAX || augment(cell_of[d], doms, digit_of, cell_of, seen) {
+            digit_of[c] = d;
+            cell_of[d] = c;
+            return true;
+        }
+    }
+    false
+}
+
+/// Tarjan's SCC labels for a small graph.
+fn strongly_connected(adj: &[Vec<usize>]) -> Vec<usize> {
+    struct Tarjan<'a> {
+        adj: &'a [Vec<usize>],
+        index: Vec<usize>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        comp: Vec<usize>,
+        next_index: usize,
+        next_comp: usize,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, v: usize) {
+            self.index[v] = self.next_index;
+            self.low[v] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+            for k in 0..self.adj[v].len() {
+                let w = self.adj[v][k];
+                if self.index[w] == usize::MAX {
+                    self.visit(w);
+                    self.low[v] = self.low[v].min(self.low[w]);
+                } else if self.on_stack[w] {
+                    self.low[v] = self.low[v].min(self.index[w]);
+                }
+            }
+            if self.low[v] == self.index[v] {
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    self.comp[w] = self.next_comp;
+                    if w == v {
+                        break;
+                    }
+                }
+                self.next_comp += 1;
+            }
+        }
+    }
+
+    let n = adj.len();
+    let mut t = Tarjan {
+        adj,
+        index: vec![usize::MAX; n],
+        low: vec![0; n],
+        on_stack: vec![false; n],
+        stack: Vec::with_capacity(n),
+        comp: vec![0; n],
+        next_index: 0,
+        next_comp: 0,
+    };
+    for v in 0..n {
+        if t.index[v] == usize::MAX {
+            t.visit(v);
+        }
+    }
+    t.comp
+}
+
+/// Pointing/claiming across two all-different houses: a digit that house
+/// `a` can only place in the overlap with `b` is gone from the rest of `b`.
+pub(crate) fn propagate_intersection(
+    st: &mut State,
+    a: &[CellIx; 9],
+    b: &[CellIx; 9],
+) -> Result<bool, Contradiction> {
+    let mut inside: Domain = 0;
+    let mut outside: Domain = 0;
+    for &i in a {
+        if b.contains(&i) {
+            inside |= st.domains[i as usize];
+        } else {
+            outside |= st.domains[i as usize];
+        }
+    }
+    let confined = inside & !outside;
+    if confined == 0 {
+        return Ok(false);
+    }
+
+    let mut changed = false;
+    for &i in b {
+        if !a.contains(&i) && st.narrow(i, !confined)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Learned nogood: the assignments `cell = digit` can't all hold. Once all
+/// but one hold, the last digit is removed from its cell.
+fn propagate_nogood(st: &mut State, lits: &[(CellIx, u8)]) -> Result<bool, Contradiction> {
+    let mut open = None;
+    for &(i, d) in lits {
+        let di = st.domains[i as usize];
+        let bit = 1u16 << d;
+        if di & bit == 0 {
+            return Ok(false);
+        }
+        if di != bit {
+            if open.is_some() {
+                return Ok(false);
+            }
+            open = Some((i, bit));
+        }
+    }
+    match open {
+        None => Err(Contradiction),
+        Some((i, bit)) => st.narrow(i, !bit),
+    }
+}
+
 fn propagate_kropki_white(st: &mut State, a: CellIx, b: CellIx) -> Result<bool, Contradiction> {
     let da = st.domains[a as usize];
     let db = st.domains[b as usize];
@@ -136,54 +631,1152 @@ fn propagate_kropki_black(st: &mut State, a: CellIx, b: CellIx) -> Result<bool,
     Ok(changed)
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::types::bit_of_digit;
+/// Sum bounds without distinctness: each cell must fit between the sum minus
+/// the others' max and the sum minus the others' min.
+fn propagate_sum(st: &mut State, cells: &[CellIx], sum: u8) -> Result<bool, Contradiction> {
+    propagate_sum_range(st, cells, sum as i32, sum as i32)
+}
 
-    use super::*;
+/// Like [`propagate_sum`] with the sum only known to lie in `lo..=hi`.
+fn propagate_sum_range(
+    st: &mut State,
+    cells: &[CellIx],
+    sum_lo: i32,
+    sum_hi: i32,
+) -> Result<bool, Contradiction> {
+    let (lo, hi) = sum_bounds(st, cells)?;
+    if sum_hi < lo || sum_lo > hi {
+        return Err(Contradiction);
+    }
 
-    fn mask(digits: &[u8]) -> Domain {
-        digits.iter().fold(0, |acc, &d| acc | bit_of_digit(d))
+    let mut changed = false;
+    for &i in cells {
+        let di = st.domains[i as usize];
+        let others_lo = lo - min_digit(di) as i32;
+        let others_hi = hi - max_digit(di) as i32;
+        if st.narrow(i, range_mask(sum_lo - others_hi, sum_hi - others_lo))? {
+            changed = true;
+        }
     }
+    Ok(changed)
+}
 
-    #[test]
-    fn test_all_diff_eliminates_taken_digits_from_peers() {
-        let mut st = State::new();
+fn sum_bounds(st: &State, cells: &[CellIx]) -> Result<(i32, i32), Contradiction> {
+    let mut lo = 0i32;
+    let mut hi = 0i32;
+    for &i in cells {
+        let di = st.domains[i as usize];
+        if di == 0 {
+            return Err(Contradiction);
+        }
+        lo += min_digit(di) as i32;
+        hi += max_digit(di) as i32;
+    }
+    Ok((lo, hi))
+}
 
-        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+/// Region sum line: box borders cut the line into segments that all share
+/// one sum. The shared sum lies in the intersection of every segment's
+/// bounds, which then feeds back into each segment.
+fn propagate_region_sum(st: &mut State, cells: &[CellIx]) -> Result<bool, Contradiction> {
+    let segments: Vec<&[CellIx]> = cells.chunk_by(|&x, &y| box_of(x) == box_of(y)).collect();
+    if segments.len() < 2 {
+        return Ok(false);
+    }
 
-        // cell 0 is set to 5
-        st.domains[0] = mask(&[5]);
+    let mut sum_lo = 0i32;
+    let mut sum_hi = i32::MAX;
+    for seg in &segments {
+        let (lo, hi) = sum_bounds(st, seg)?;
+        sum_lo = sum_lo.max(lo);
+        sum_hi = sum_hi.min(hi);
+    }
+    if sum_lo > sum_hi {
+        return Err(Contradiction);
+    }
 
-        let changed = propagate_all_diff(&mut st, &cells).unwrap();
-        assert!(changed);
+    let mut changed = false;
+    for seg in &segments {
+        if propagate_sum_range(st, seg, sum_lo, sum_hi)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
 
-        // cell 0 is still 5
-        assert_eq!(st.domains[0], mask(&[5]));
+/// Every run of three adjacent cells holds one digit from each class. That
+/// makes positions three apart share a class, and the three position groups
+/// take all-different classes.
+fn propagate_class_line(
+    st: &mut State,
+    cells: &[CellIx],
+    classes: &[Domain; 3],
+) -> Result<bool, Contradiction> {
+    if cells.len() < 3 {
+        return Ok(false);
+    }
 
-        // all other cells do not contain 5 anymore
-        let five = mask(&[5]);
-        for &i in &cells[1..] {
-            assert_eq!(st.domains[i as usize] & five, 0, "cell {} still has 5", i);
+    // bit k set when class k is still possible for the whole group
+    let mut group = [0b111u8; 3];
+    for (p, &i) in cells.iter().enumerate() {
+        let di = st.domains[i as usize];
+        let mut here = 0u8;
+        for (k, &cls) in classes.iter().enumerate() {
+            if di & cls != 0 {
+                here |= 1 << k;
+            }
         }
+        group[p % 3] &= here;
     }
 
-    #[test]
-    fn test_all_diff_finds_hidden_single() {
-        let mut st = State::new();
+    // all-different over 3 groups and 3 classes: naked and hidden singles
+    loop {
+        let mut progress = false;
+        for g in 0..3 {
+            if group[g] == 0 {
+                return Err(Contradiction);
+            }
+            let fixed = group[g];
+            if fixed.count_ones() == 1 {
+                for (h, other) in group.iter_mut().enumerate() {
+                    if h != g && *other & fixed != 0 {
+                        *other &= !fixed;
+                        progress = true;
+                    }
+                }
+            }
+        }
+        for k in 0..3 {
+            let bit = 1u8 << k;
+            let holders: Vec<usize> = (0..3).filter(|&g| group[g] & bit != 0).collect();
+            if holders.is_empty() {
+                return Err(Contradiction);
+            }
+            if holders.len() == 1 && group[holders[0]] != bit {
+                group[holders[0]] = bit;
+                progress = true;
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
 
-        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+    let mut changed = false;
+    for (p, &i) in cells.iter().enumerate() {
+        let mut mask: Domain = 0;
+        for (k, &cls) in classes.iter().enumerate() {
+            if group[p % 3] & (1 << k) != 0 {
+                mask |= cls;
+            }
+        }
+        if st.narrow(i, mask)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
 
-        // Remove digit 9 from cells 0..7, so only cell 8 can be 9
-        let nine = mask(&[9]);
-        for i in 0..8 {
-            st.domains[i] &= !nine;
+/// Sums reachable by a pair, as bits 2..=18 of a u32: `db` shifted by each
+/// digit of `da`.
+fn pair_sums(da: Domain, db: Domain) -> u32 {
+    let mut sums = 0u32;
+    let mut m = da;
+    while m != 0 {
+        let d = m.trailing_zeros();
+        m &= !(1u16 << d);
+        sums |= (db as u32) << d;
+    }
+    sums
+}
+
+/// Digits of `da` that reach one of `sums` with some digit of `db`.
+fn sum_support(da: Domain, db: Domain, sums: u32) -> Domain {
+    let mut keep: Domain = 0;
+    let mut m = da;
+    while m != 0 {
+        let d = m.trailing_zeros();
+        m &= !(1u16 << d);
+        if ((db as u32) << d) & sums != 0 {
+            keep |= 1u16 << d;
         }
+    }
+    keep
+}
 
-        let changed = propagate_all_diff(&mut st, &cells).unwrap();
-        assert!(changed);
+/// Zipper line: cells mirrored about the centre sum to the same value, which
+/// is the centre digit on odd-length lines.
+fn propagate_zipper(st: &mut State, cells: &[CellIx]) -> Result<bool, Contradiction> {
+    let n = cells.len();
+    let centre = if n % 2 == 1 { Some(cells[n / 2]) } else { None };
 
-        // cell 8 must be 9
-        assert_eq!(st.domains[8], nine);
+    let mut sums = match centre {
+        Some(c) => st.domains[c as usize] as u32,
+        None => u32::MAX,
+    };
+    for k in 0..n / 2 {
+        let dx = st.domains[cells[k] as usize];
+        let dy = st.domains[cells[n - 1 - k] as usize];
+        sums &= pair_sums(dx, dy);
+    }
+    if sums == 0 {
+        return Err(Contradiction);
+    }
+
+    let mut changed = false;
+    if let Some(c) = centre
+        && st.narrow(c, sums as Domain)?
+    {
+        changed = true;
+    }
+    for k in 0..n / 2 {
+        let (x, y) = (cells[k], cells[n - 1 - k]);
+        let dx = st.domains[x as usize];
+        let dy = st.domains[y as usize];
+        if st.narrow(x, sum_support(dx, dy, sums))? {
+            changed = true;
+        }
+        if st.narrow(y, sum_support(dy, dx, sums))? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// X-sum clue on a house read from the clue side: the first X digits sum to
+/// `sum`, where X is the first digit. Each candidate X is checked against the
+/// prefix bounds (and the smallest/largest distinct digits), and once X is
+/// known the prefix gets ordinary sum bounds.
+fn propagate_x_sum(st: &mut State, cells: &[CellIx; 9], sum: u8) -> Result<bool, Contradiction> {
+    let sum = sum as i32;
+    let first = st.domains[cells[0] as usize];
+    let mut keep: Domain = 0;
+    let mut m = first;
+    while m != 0 {
+        let x = m.trailing_zeros() as i32;
+        m &= !(1u16 << x);
+
+        let rest = &cells[1..x as usize];
+        let (lo, hi) = sum_bounds(st, rest)?;
+        let others = (1..=9).filter(|&d| d != x);
+        let distinct_lo: i32 = others.clone().take(rest.len()).sum();
+        let distinct_hi: i32 = others.rev().take(rest.len()).sum();
+        let target = sum - x;
+        if target >= lo.max(distinct_lo) && target <= hi.min(distinct_hi) {
+            keep |= 1u16 << x;
+        }
+    }
+
+    let mut changed = st.narrow(cells[0], keep)?;
+    let first = st.domains[cells[0] as usize];
+    if first.count_ones() == 1 {
+        let x = first.trailing_zeros() as usize;
+        if propagate_sum(st, &cells[..x], sum as u8)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Skyscraper clue on a house read from the clue side: `visible` digits are
+/// taller than everything before them. Cell k can be at most 9 - visible + 1
+/// + k, and the solved prefix bounds how many more can still be seen.
+fn propagate_skyscraper(
+    st: &mut State,
+    cells: &[CellIx; 9],
+    visible: u8,
+) -> Result<bool, Contradiction> {
+    let v = visible as i32;
+    let mut changed = false;
+    for (k, &i) in cells.iter().enumerate() {
+        if st.narrow(i, range_mask(1, 9 - v + 1 + k as i32))? {
+            changed = true;
+        }
+    }
+
+    let mut seen = 0i32;
+    let mut tallest = 0i32;
+    for &i in cells {
+        let di = st.domains[i as usize];
+        if di.count_ones() != 1 {
+            break;
+        }
+        let d = di.trailing_zeros() as i32;
+        if d > tallest {
+            seen += 1;
+            tallest = d;
+        }
+    }
+    // each height above the tallest so far can add at most one more
+    if seen > v || seen + (9 - tallest) < v {
+        return Err(Contradiction);
+    }
+    Ok(changed)
+}
+
+/// Indexing: the digit k in `index_cell` says `digit` sits at `house[k - 1]`.
+/// `digit` appears exactly once in the house, so the index domain and the
+/// positions that can hold `digit` channel into each other.
+fn propagate_index(
+    st: &mut State,
+    index_cell: CellIx,
+    house: &[CellIx; 9],
+    digit: u8,
+) -> Result<bool, Contradiction> {
+    let bit = 1u16 << digit;
+
+    let mut can_hold: Domain = 0;
+    for (p, &i) in house.iter().enumerate() {
+        if st.domains[i as usize] & bit != 0 {
+            can_hold |= 1u16 << (p + 1);
+        }
+    }
+    let mut changed = st.narrow(index_cell, can_hold)?;
+
+    let index = st.domains[index_cell as usize];
+    for (p, &i) in house.iter().enumerate() {
+        let at = 1u16 << (p + 1);
+        if index & at == 0 {
+            if st.narrow(i, !bit)? {
+                changed = true;
+            }
+        } else if index == at && st.assign(i, bit)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Extensional constraint: the cells must match one of `tuples`. Tuples
+/// that still fit the current domains give each cell its supported digits
+/// (generalized arc consistency). Malformed tuples, of the wrong length or
+/// with a digit outside 1-9, never fit.
+fn propagate_table(
+    st: &mut State,
+    cells: &[CellIx],
+    tuples: &[Vec<u8>],
+) -> Result<bool, Contradiction> {
+    let mut support = vec![0 as Domain; cells.len()];
+    for t in tuples {
+        if t.len() != cells.len() || !t.iter().all(|d| (1..=9).contains(d)) {
+            continue;
+        }
+        let fits = t
+            .iter()
+            .zip(cells)
+            .all(|(&d, &i)| st.domains[i as usize] & (1u16 << d) != 0);
+        if fits {
+            for (k, &d) in t.iter().enumerate() {
+                support[k] |= 1u16 << d;
+            }
+        }
+    }
+
+    let mut changed = false;
+    for (k, &i) in cells.iter().enumerate() {
+        if st.narrow(i, support[k])? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Once `cell` is decided, its digit leaves every cell a move away. Each
+/// cell gets its own constraint, woken only by changes to that cell.
+fn propagate_anti_move(
+    st: &mut State,
+    cell: CellIx,
+    moves: &[(isize, isize)],
+) -> Result<bool, Contradiction> {
+    let di = st.domains[cell as usize];
+    if di.count_ones() != 1 {
+        return Ok(false);
+    }
+    let mut changed = false;
+    for &mv in moves {
+        if let Some(j) = step(cell, mv)
+            && st.narrow(j, !di)?
+        {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// The inverse of the Kropki white reach: a neighbour value is only kept if
+/// some digit of `cell` is not next to it. Cells with 3+ candidates always
+/// leave a support, so only near-solved cells do any work.
+fn propagate_non_consecutive(st: &mut State, cell: CellIx) -> Result<bool, Contradiction> {
+    let di = st.domains[cell as usize];
+    if di.count_ones() > 2 {
+        return Ok(false);
+    }
+    let mut allowed: Domain = 0;
+    for d in 1..=9u8 {
+        let bit = 1u16 << d;
+        let adjacent = ((bit << 1) | (bit >> 1)) & DIGITS_MASK;
+        if di & !adjacent != 0 {
+            allowed |= bit;
+        }
+    }
+    let mut changed = false;
+    for &mv in &ORTHOGONAL_MOVES {
+        if let Some(j) = step(cell, mv)
+            && st.narrow(j, allowed)?
+        {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// a < b: a stays below b's max, b stays above a's min.
+fn propagate_less(st: &mut State, a: CellIx, b: CellIx) -> Result<bool, Contradiction> {
+    let da = st.domains[a as usize];
+    let db = st.domains[b as usize];
+    if da == 0 || db == 0 {
+        return Err(Contradiction);
+    }
+
+    let mut changed = false;
+    if st.narrow(a, range_mask(1, max_digit(db) as i32 - 1))? {
+        changed = true;
+    }
+    let da = st.domains[a as usize];
+    if st.narrow(b, range_mask(min_digit(da) as i32 + 1, 9))? {
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Strictly increasing from bulb to tip: one forward pass pushes minimums up,
+/// one backward pass pulls maximums down.
+fn propagate_thermo(st: &mut State, cells: &[CellIx]) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for w in cells.windows(2) {
+        if propagate_less(st, w[0], w[1])? {
+            changed = true;
+        }
+    }
+    for w in cells.windows(2).rev() {
+        if propagate_less(st, w[0], w[1])? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Every listed digit (with multiplicity) appears among the four cells. A
+/// digit that fits in exactly as many cells as it is needed is placed there;
+/// four listed digits leave no room for anything else.
+fn propagate_quadruple(
+    st: &mut State,
+    cells: &[CellIx; 4],
+    digits: &[u8],
+) -> Result<bool, Contradiction> {
+    let mut changed = false;
+
+    let mut need = [0u8; 10];
+    let mut required: Domain = 0;
+    for &d in digits {
+        need[d as usize] += 1;
+        required |= 1u16 << d;
+    }
+
+    let mut m = required;
+    while m != 0 {
+        let d = m.trailing_zeros() as u8;
+        m &= !(1u16 << d);
+        let bit = 1u16 << d;
+        let fits = cells
+            .iter()
+            .filter(|&&i| st.domains[i as usize] & bit != 0)
+            .count() as u8;
+        if fits < need[d as usize] {
+            return Err(Contradiction);
+        }
+        if fits == need[d as usize] {
+            for &i in cells {
+                if st.domains[i as usize] & bit != 0 && st.assign(i, bit)? {
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if digits.len() == cells.len() {
+        for &i in cells {
+            if st.narrow(i, required)? {
+                changed = true;
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Cell equality: both cells of each pair keep only their common digits.
+fn propagate_equal_pairs(
+    st: &mut State,
+    pairs: impl Iterator<Item = (CellIx, CellIx)>,
+) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for (x, y) in pairs {
+        let common = st.domains[x as usize] & st.domains[y as usize];
+        if st.narrow(x, common)? {
+            changed = true;
+        }
+        if st.narrow(y, common)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Between line: middle digits lie strictly between the two ends. Either
+/// end may be the low one, so each orientation is narrowed on its own and the
+/// results are unioned.
+fn propagate_between(
+    st: &mut State,
+    end_a: CellIx,
+    end_b: CellIx,
+    middle: &[CellIx],
+) -> Result<bool, Contradiction> {
+    // the low end sits below every middle cell's max, the high end above
+    // every middle cell's min
+    let mut low_below = 10i32;
+    let mut high_above = 0i32;
+    for &i in middle {
+        let di = st.domains[i as usize];
+        if di == 0 {
+            return Err(Contradiction);
+        }
+        low_below = low_below.min(max_digit(di) as i32);
+        high_above = high_above.max(min_digit(di) as i32);
+    }
+
+    let da = st.domains[end_a as usize];
+    let db = st.domains[end_b as usize];
+    let mut keep_a: Domain = 0;
+    let mut keep_b: Domain = 0;
+    let mut keep_middle: Domain = 0;
+    for (d_lo, d_hi, flipped) in [(da, db, false), (db, da, true)] {
+        if d_lo == 0 || d_hi == 0 {
+            return Err(Contradiction);
+        }
+        let lo = d_lo & range_mask(1, low_below.min(max_digit(d_hi) as i32) - 1);
+        if lo == 0 {
+            continue;
+        }
+        let hi = d_hi & range_mask(high_above.max(min_digit(lo) as i32) + 1, 9);
+        if hi == 0 {
+            continue;
+        }
+        let lo = lo & range_mask(1, max_digit(hi) as i32 - 1);
+        if lo == 0 {
+            continue;
+        }
+        keep_middle |= range_mask(min_digit(lo) as i32 + 1, max_digit(hi) as i32 - 1);
+        if flipped {
+            keep_a |= hi;
+            keep_b |= lo;
+        } else {
+            keep_a |= lo;
+            keep_b |= hi;
+        }
+    }
+
+    let mut changed = false;
+    if st.narrow(end_a, keep_a)? {
+        changed = true;
+    }
+    if st.narrow(end_b, keep_b)? {
+        changed = true;
+    }
+    for &i in middle {
+        if st.narrow(i, keep_middle)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Lockout line: the ends differ by at least `min_diff` and middle digits
+/// lie outside the ends' closed range. A middle digit is only ruled out when
+/// it is inside the range for every choice of ends, i.e. between the low
+/// end's max and the high end's min.
+fn propagate_lockout(
+    st: &mut State,
+    end_a: CellIx,
+    end_b: CellIx,
+    middle: &[CellIx],
+    min_diff: u8,
+) -> Result<bool, Contradiction> {
+    let diff = min_diff as i32;
+    let da = st.domains[end_a as usize];
+    let db = st.domains[end_b as usize];
+    let mut keep_a: Domain = 0;
+    let mut keep_b: Domain = 0;
+    let mut keep_middle: Domain = 0;
+    for (d_lo, d_hi, flipped) in [(da, db, false), (db, da, true)] {
+        if d_lo == 0 || d_hi == 0 {
+            return Err(Contradiction);
+        }
+        let lo = d_lo & range_mask(1, max_digit(d_hi) as i32 - diff);
+        if lo == 0 {
+            continue;
+        }
+        let hi = d_hi & range_mask(min_digit(lo) as i32 + diff, 9);
+        if hi == 0 {
+            continue;
+        }
+        keep_middle |= DIGITS_MASK & !range_mask(max_digit(lo) as i32, min_digit(hi) as i32);
+        if flipped {
+            keep_a |= hi;
+            keep_b |= lo;
+        } else {
+            keep_a |= lo;
+            keep_b |= hi;
+        }
+    }
+
+    let mut changed = false;
+    if st.narrow(end_a, keep_a)? {
+        changed = true;
+    }
+    if st.narrow(end_b, keep_b)? {
+        changed = true;
+    }
+    for &i in middle {
+        if st.narrow(i, keep_middle)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::bit_of_digit;
+
+    use super::*;
+
+    fn mask(digits: &[u8]) -> Domain {
+        digits.iter().fold(0, |acc, &d| acc | bit_of_digit(d))
+    }
+
+    #[test]
+    fn test_all_diff_eliminates_taken_digits_from_peers() {
+        let mut st = State::new();
+
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // cell 0 is set to 5
+        st.domains[0] = mask(&[5]);
+
+        let changed = propagate_all_diff(&mut st, &cells).unwrap();
+        assert!(changed);
+
+        // cell 0 is still 5
+        assert_eq!(st.domains[0], mask(&[5]));
+
+        // all other cells do not contain 5 anymore
+        let five = mask(&[5]);
+        for &i in &cells[1..] {
+            assert_eq!(st.domains[i as usize] & five, 0, "cell {} still has 5", i);
+        }
+    }
+
+    #[test]
+    fn test_all_diff_finds_hidden_single() {
+        let mut st = State::new();
+
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // Remove digit 9 from cells 0..7, so only cell 8 can be 9
+        let nine = mask(&[9]);
+        for i in 0..8 {
+            st.domains[i] &= !nine;
+        }
+
+        let changed = propagate_all_diff(&mut st, &cells).unwrap();
+        assert!(changed);
+
+        // cell 8 must be 9
+        assert_eq!(st.domains[8], nine);
+    }
+
+    #[test]
+    fn test_sum_bounds_narrow_cells() {
+        let mut st = State::new();
+        let cells: [CellIx; 3] = [0, 10, 20];
+
+        // 3 cells summing to 5 leaves each cell in 1..=3
+        let changed = propagate_sum(&mut st, &cells, 5).unwrap();
+        assert!(changed);
+        for &i in &cells {
+            assert_eq!(st.domains[i as usize], mask(&[1, 2, 3]));
+        }
+
+        // repeats are fine: 1 + 1 + 3
+        st.domains[0] = mask(&[1]);
+        st.domains[10] = mask(&[1]);
+        propagate_sum(&mut st, &cells, 5).unwrap();
+        assert_eq!(st.domains[20], mask(&[3]));
+    }
+
+    #[test]
+    fn test_sum_out_of_range_is_contradiction() {
+        let mut st = State::new();
+        let cells: [CellIx; 2] = [0, 10];
+        assert!(propagate_sum(&mut st, &cells, 19).is_err());
+        assert!(propagate_sum(&mut st, &cells, 1).is_err());
+    }
+
+    #[test]
+    fn test_anti_knight_removes_digit_from_knight_cells() {
+        let mut st = State::new();
+        // r4c4 = 5
+        st.domains[40] = mask(&[5]);
+
+        let changed = propagate_anti_move(&mut st, 40, &KNIGHT_MOVES).unwrap();
+        assert!(changed);
+
+        let five = mask(&[5]);
+        for i in [21u8, 23, 29, 33, 47, 51, 57, 59] {
+            assert_eq!(st.domains[i as usize] & five, 0, "cell {} still has 5", i);
+        }
+        // king neighbours are untouched
+        assert_eq!(st.domains[31], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_anti_king_clash_is_contradiction() {
+        let mut st = State::new();
+        st.domains[0] = mask(&[3]);
+        st.domains[10] = mask(&[3]);
+        assert!(propagate_anti_move(&mut st, 0, &KING_MOVES).is_err());
+    }
+
+    #[test]
+    fn test_non_consecutive_singleton_and_pair() {
+        let mut st = State::new();
+        // r0c0 = 5 removes 4 and 6 from r0c1 and r1c0
+        st.domains[0] = mask(&[5]);
+        // r4c4 in {3,5} removes only 4 from its neighbours
+        st.domains[40] = mask(&[3, 5]);
+
+        assert!(propagate_non_consecutive(&mut st, 0).unwrap());
+        assert!(propagate_non_consecutive(&mut st, 40).unwrap());
+        // a wide domain gives no support information
+        assert!(!propagate_non_consecutive(&mut st, 20).unwrap());
+
+        let all_but = |ds: &[u8]| DIGITS_MASK & !mask(ds);
+        assert_eq!(st.domains[1], all_but(&[4, 6]));
+        assert_eq!(st.domains[9], all_but(&[4, 6]));
+        for i in [31, 39, 41, 49] {
+            assert_eq!(st.domains[i], all_but(&[4]));
+        }
+        // diagonal neighbour untouched
+        assert_eq!(st.domains[10], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_less_bounds() {
+        let mut st = State::new();
+        st.domains[0] = mask(&[4, 6, 8]);
+        st.domains[1] = mask(&[2, 3, 7]);
+
+        let changed = propagate_less(&mut st, 0, 1).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[0], mask(&[4, 6]));
+        assert_eq!(st.domains[1], mask(&[7]));
+
+        st.domains[0] = mask(&[9]);
+        assert!(propagate_less(&mut st, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_thermo_forces_full_length_line() {
+        let mut st = State::new();
+        let cells: Vec<CellIx> = (0..9).collect();
+
+        let changed = propagate_thermo(&mut st, &cells).unwrap();
+        assert!(changed);
+        for (k, &i) in cells.iter().enumerate() {
+            assert_eq!(st.domains[i as usize], mask(&[k as u8 + 1]));
+        }
+    }
+
+    #[test]
+    fn test_quadruple_places_confined_digit() {
+        let mut st = State::new();
+        let cells: [CellIx; 4] = [0, 1, 9, 10];
+        // 7 needed twice but only fits in cells 0 and 10
+        st.domains[1] &= !mask(&[7]);
+        st.domains[9] &= !mask(&[7]);
+
+        let changed = propagate_quadruple(&mut st, &cells, &[7, 7]).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[0], mask(&[7]));
+        assert_eq!(st.domains[10], mask(&[7]));
+
+        st.domains[10] = mask(&[1]);
+        assert!(propagate_quadruple(&mut st, &cells, &[7, 7]).is_err());
+    }
+
+    #[test]
+    fn test_quadruple_saturated_restricts_cells() {
+        let mut st = State::new();
+        let cells: [CellIx; 4] = [0, 1, 9, 10];
+
+        propagate_quadruple(&mut st, &cells, &[1, 2, 3, 4]).unwrap();
+        for &i in &cells {
+            assert_eq!(st.domains[i as usize], mask(&[1, 2, 3, 4]));
+        }
+    }
+
+    #[test]
+    fn test_palindrome_intersects_mirrored_cells() {
+        let mut st = State::new();
+        let c = Constraint::Palindrome {
+            cells: vec![0, 1, 2, 3, 4],
+        };
+        st.domains[0] = mask(&[1, 2, 3]);
+        st.domains[4] = mask(&[2, 3, 4]);
+        st.domains[1] = mask(&[5]);
+
+        let changed = c.propagate(&mut st).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[0], mask(&[2, 3]));
+        assert_eq!(st.domains[4], mask(&[2, 3]));
+        assert_eq!(st.domains[3], mask(&[5]));
+        // the middle cell pairs with nothing
+        assert_eq!(st.domains[2], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_clone_disjoint_domains_is_contradiction() {
+        let mut st = State::new();
+        let c = Constraint::Clone {
+            a: vec![0, 1],
+            b: vec![60, 61],
+        };
+        st.domains[1] = mask(&[1, 2]);
+        st.domains[61] = mask(&[8, 9]);
+        assert!(c.propagate(&mut st).is_err());
+    }
+
+    #[test]
+    fn test_clone_regions_of_different_lengths_is_contradiction() {
+        let mut st = State::new();
+        let c = Constraint::Clone {
+            a: vec![0, 1, 2],
+            b: vec![60, 61],
+        };
+        assert!(c.propagate(&mut st).is_err());
+    }
+
+    #[test]
+    fn test_between_narrows_middle_and_ends() {
+        let mut st = State::new();
+        st.domains[0] = mask(&[2, 3]);
+        st.domains[4] = mask(&[6, 7, 8]);
+        st.domains[2] = mask(&[5, 6]);
+
+        let changed = propagate_between(&mut st, 0, 4, &[1, 2, 3]).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[1], mask(&[3, 4, 5, 6, 7]));
+        assert_eq!(st.domains[3], mask(&[3, 4, 5, 6, 7]));
+        // the high end must exceed the middle 5
+        assert_eq!(st.domains[4], mask(&[6, 7, 8]));
+
+        // ends next to each other leave no room
+        st.domains[0] = mask(&[4]);
+        st.domains[4] = mask(&[5]);
+        assert!(propagate_between(&mut st, 0, 4, &[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_lockout_excludes_range_between_ends() {
+        let mut st = State::new();
+        st.domains[0] = mask(&[2]);
+        st.domains[4] = mask(&[7, 8]);
+
+        let changed = propagate_lockout(&mut st, 0, 4, &[1, 2, 3], 4).unwrap();
+        assert!(changed);
+        for i in 1..4 {
+            assert_eq!(st.domains[i], mask(&[1, 8, 9]));
+        }
+
+        // ends closer than the minimum difference
+        st.domains[4] = mask(&[5]);
+        assert!(propagate_lockout(&mut st, 0, 4, &[1, 2, 3], 4).is_err());
+    }
+
+    #[test]
+    fn test_region_sum_shares_bounds_across_segments() {
+        let mut st = State::new();
+        // r0c1, r0c2 | r0c3: the single cell in box 1 bounds the pair
+        let cells: [CellIx; 3] = [1, 2, 3];
+        st.domains[3] = mask(&[3, 4]);
+
+        let changed = propagate_region_sum(&mut st, &cells).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[1], mask(&[1, 2, 3]));
+        assert_eq!(st.domains[2], mask(&[1, 2, 3]));
+
+        st.domains[1] = mask(&[3]);
+        st.domains[2] = mask(&[3]);
+        assert!(propagate_region_sum(&mut st, &cells).is_err());
+    }
+
+    #[test]
+    fn test_entropic_groups_share_class() {
+        let mut st = State::new();
+        let cells: [CellIx; 5] = [0, 1, 2, 3, 4];
+        st.domains[0] = mask(&[2]);
+        st.domains[4] = mask(&[5, 8]);
+
+        let changed = propagate_class_line(&mut st, &cells, &ENTROPY_CLASSES).unwrap();
+        assert!(changed);
+        // cell 3 is three away from the low cell 0
+        assert_eq!(st.domains[3], LOW_MASK);
+        // cell 4 can't be low, and cell 1 shares its group
+        assert_eq!(st.domains[1], MID_MASK | HIGH_MASK);
+        assert_eq!(st.domains[2], MID_MASK | HIGH_MASK);
+    }
+
+    #[test]
+    fn test_modular_clash_is_contradiction() {
+        let mut st = State::new();
+        let cells: [CellIx; 3] = [0, 1, 2];
+        st.domains[0] = mask(&[1]);
+        st.domains[2] = mask(&[4, 7]);
+        assert!(propagate_class_line(&mut st, &cells, &MOD3_CLASSES).is_err());
+    }
+
+    #[test]
+    fn test_zipper_odd_line_sums_to_centre() {
+        let mut st = State::new();
+        let cells: [CellIx; 5] = [0, 1, 2, 3, 4];
+        st.domains[2] = mask(&[4]);
+        st.domains[0] = mask(&[1]);
+
+        let changed = propagate_zipper(&mut st, &cells).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[4], mask(&[3]));
+        assert_eq!(st.domains[1], mask(&[1, 2, 3]));
+        assert_eq!(st.domains[3], mask(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_zipper_even_line_shares_sum() {
+        let mut st = State::new();
+        let cells: [CellIx; 4] = [0, 1, 2, 3];
+        st.domains[1] = mask(&[9]);
+        st.domains[2] = mask(&[8]);
+        st.domains[0] = mask(&[7, 8, 9]);
+
+        propagate_zipper(&mut st, &cells).unwrap();
+        // outer pair must also sum to 17
+        assert_eq!(st.domains[0], mask(&[8, 9]));
+        assert_eq!(st.domains[3], mask(&[8, 9]));
+
+        st.domains[3] = mask(&[1]);
+        assert!(propagate_zipper(&mut st, &cells).is_err());
+    }
+
+    #[test]
+    fn test_x_sum_filters_first_digit() {
+        let mut st = State::new();
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // sum 1 only works with X = 1
+        propagate_x_sum(&mut st, &cells, 1).unwrap();
+        assert_eq!(st.domains[0], mask(&[1]));
+
+        // X = 3 fixed, sum 6: the next two cells hold 1 and 2
+        let mut st = State::new();
+        st.domains[0] = mask(&[3]);
+        let changed = propagate_x_sum(&mut st, &cells, 6).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[1], mask(&[1, 2]));
+        assert_eq!(st.domains[2], mask(&[1, 2]));
+        assert_eq!(st.domains[3], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_skyscraper_bounds_and_count() {
+        let mut st = State::new();
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // seeing all nine: cell k is at most k + 1
+        propagate_skyscraper(&mut st, &cells, 9).unwrap();
+        assert_eq!(st.domains[0], mask(&[1]));
+        assert_eq!(st.domains[2], mask(&[1, 2, 3]));
+
+        // 9 up front hides everything after it
+        let mut st = State::new();
+        st.domains[0] = mask(&[9]);
+        assert!(propagate_skyscraper(&mut st, &cells, 2).is_err());
+        let mut st = State::new();
+        st.domains[0] = mask(&[9]);
+        assert!(propagate_skyscraper(&mut st, &cells, 1).is_ok());
+    }
+
+    #[test]
+    fn test_index_channels_both_ways() {
+        let mut st = State::new();
+        let house: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // 1 can't go in columns 3..=9, so r0c1 indexes column 1 or 2
+        for i in 2..9 {
+            st.domains[i] &= !mask(&[1]);
+        }
+        let changed = propagate_index(&mut st, 0, &house, 1).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[0], mask(&[1, 2]));
+
+        // r0c1 = 2 places the 1 at r0c2
+        st.domains[0] = mask(&[2]);
+        propagate_index(&mut st, 0, &house, 1).unwrap();
+        assert_eq!(st.domains[1], mask(&[1]));
+    }
+
+    #[test]
+    fn test_index_scope_lists_each_cell_once() {
+        let house: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        let c = Constraint::Index {
+            index_cell: 4,
+            house,
+            digit: 5,
+        };
+        assert_eq!(c.scope().collect::<Vec<_>>(), house);
+    }
+
+    #[test]
+    fn test_index_removes_digit_outside_index_domain() {
+        let mut st = State::new();
+        let house: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        st.domains[4] = mask(&[3, 7]);
+
+        propagate_index(&mut st, 4, &house, 5).unwrap();
+        for i in [0, 1, 3, 5, 7, 8] {
+            assert_eq!(st.domains[i] & mask(&[5]), 0, "cell {} still has 5", i);
+        }
+        assert_ne!(st.domains[2] & mask(&[5]), 0);
+    }
+
+    #[test]
+    fn test_table_keeps_supported_digits() {
+        let mut st = State::new();
+        let cells: [CellIx; 2] = [0, 1];
+        let tuples = vec![vec![1, 2], vec![3, 4], vec![5, 6]];
+        st.domains[1] = mask(&[2, 6, 9]);
+
+        let changed = propagate_table(&mut st, &cells, &tuples).unwrap();
+        assert!(changed);
+        assert_eq!(st.domains[0], mask(&[1, 5]));
+        assert_eq!(st.domains[1], mask(&[2, 6]));
+
+        st.domains[0] = mask(&[3]);
+        assert!(propagate_table(&mut st, &cells, &tuples).is_err());
+    }
+
+    #[test]
+    fn test_table_ignores_malformed_tuples() {
+        let mut st = State::new();
+        let cells: [CellIx; 2] = [0, 1];
+        let tuples = vec![vec![1, 2], vec![3, 4, 5], vec![16, 6], vec![7]];
+
+        propagate_table(&mut st, &cells, &tuples).unwrap();
+        assert_eq!(st.domains[0], mask(&[1]));
+        assert_eq!(st.domains[1], mask(&[2]));
+    }
+
+    #[test]
+    fn test_all_diff_matching_naked_and_hidden_pairs() {
+        let mut st = State::new();
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+        // naked pair {1,2} in cells 0 and 1
+        st.domains[0] = mask(&[1, 2]);
+        st.domains[1] = mask(&[1, 2]);
+        // hidden pair: 8 and 9 only fit in cells 7 and 8
+        for i in 0..7 {
+            st.domains[i] &= !mask(&[8, 9]);
+        }
+
+        let changed = propagate_all_diff_matching(&mut st, &cells).unwrap();
+        assert!(changed);
+        for i in 2..7 {
+            assert_eq!(st.domains[i], mask(&[3, 4, 5, 6, 7]));
+        }
+        assert_eq!(st.domains[7], mask(&[8, 9]));
+        assert_eq!(st.domains[8], mask(&[8, 9]));
+    }
+
+    #[test]
+    fn test_all_diff_level_selects_filter() {
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        let run = |level| {
+            let mut st = State::new();
+            st.domains[0] = mask(&[1, 2]);
+            st.domains[1] = mask(&[1, 2]);
+            Constraint::AllDifferent { cells, level }
+                .propagate(&mut st)
+                .unwrap();
+            st.domains[2]
+        };
+        assert_eq!(run(AllDiffLevel::Basic), DIGITS_MASK);
+        assert_eq!(run(AllDiffLevel::Matching), DIGITS_MASK & !mask(&[1, 2]));
+    }
+
+    #[test]
+    fn test_all_diff_matching_detects_pigeonhole() {
+        let mut st = State::new();
+        let cells: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        for i in 0..3 {
+            st.domains[i] = mask(&[1, 2]);
+        }
+        assert!(propagate_all_diff_matching(&mut st, &cells).is_err());
+    }
+
+    #[test]
+    fn test_intersection_pointing_and_claiming() {
+        let mut st = State::new();
+        let row0: [CellIx; 9] = [0, 1, 2, 3, 4, 5, 6, 7, 8];
+        let box0: [CellIx; 9] = [0, 1, 2, 9, 10, 11, 18, 19, 20];
+
+        // pointing: 4 in box 0 only on row 0
+        for i in [9, 10, 11, 18, 19, 20] {
+            st.domains[i] &= !mask(&[4]);
+        }
+        let changed = propagate_intersection(&mut st, &box0, &row0).unwrap();
+        assert!(changed);
+        for i in 3..9 {
+            assert_eq!(st.domains[i] & mask(&[4]), 0);
+        }
+
+        // claiming: 4 in row 0 is now confined to box 0, nothing left to remove
+        assert!(!propagate_intersection(&mut st, &row0, &box0).unwrap());
+    }
+
+    #[test]
+    fn test_nogood_unit_and_conflict() {
+        let mut st = State::new();
+        let lits = vec![(0, 1), (1, 2), (2, 3)];
+        st.domains[0] = mask(&[1]);
+
+        // two literals open: nothing to do
+        assert!(!propagate_nogood(&mut st, &lits).unwrap());
+
+        st.domains[1] = mask(&[2]);
+        assert!(propagate_nogood(&mut st, &lits).unwrap());
+        assert_eq!(st.domains[2], DIGITS_MASK & !mask(&[3]));
+
+        st.domains[2] = mask(&[3]);
+        assert!(propagate_nogood(&mut st, &lits).is_err());
     }
 }
@@ -1,26 +1,134 @@
+use std::{
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+    },
+    thread,
+    time::Instant,
+};
+
 use crate::{
-    CellIx, Constraint, Contradiction, N, NN, Solve, State,
+    AllDiffLevel, CellIx, Constraint, Contradiction, Domain, EVEN_MASK, N, NN, ODD_MASK,
+    Propagator, Solve, State,
+    regions::{parse_regions, region_cells},
+    rng::Rng,
     types::{bit_of_digit, idx},
 };
 
+/// Branch budget per restart run, in units of [`RestartConfig::base`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartSchedule {
+    /// 1, 1, 2, 1, 1, 2, 4, 1, ...
+    Luby,
+    /// 1, f, f^2, ... for a factor f > 1
+    Geometric(f64),
+}
+
+impl RestartSchedule {
+    fn budget(self, run: u32) -> u32 {
+        match self {
+            RestartSchedule::Luby => luby(run),
+            RestartSchedule::Geometric(f) => f.powi(run as i32 - 1).min(u32::MAX as f64) as u32,
+        }
+    }
+}
+
+/// Luby sequence, 1-based.
+fn luby(mut i: u32) -> u32 {
+    loop {
+        let mut k = 1;
+        while (1u64 << k) - 1 < i as u64 {
+            k += 1;
+        }
+        if (1u64 << k) - 1 == i as u64 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+/// Randomized search with restarts. Value order and MRV tie-breaks come from
+/// a seeded RNG, so a seed always reproduces the same run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RestartConfig {
+    pub seed: u64,
+    pub schedule: RestartSchedule,
+    /// Branches in one schedule unit.
+    pub base: u32,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            schedule: RestartSchedule::Luby,
+            base: 100,
+        }
+    }
+}
+
 pub struct Engine {
     pub state: State,
     pub constraints: Vec<Constraint>,
+    /// Run [`Engine::probe`] at every search node before branching.
+    pub probing: bool,
+    /// Post a nogood, reduced to the decisions behind the conflict, for
+    /// failed search branches.
+    pub learning: bool,
+    all_diff_level: AllDiffLevel,
     watchers: Vec<Vec<usize>>,
     branches: u32,
+    decisions: Vec<(CellIx, u8)>,
+    /// Search root that learned nogoods are checked against.
+    root: Option<State>,
+    nogoods: usize,
+    rng: Option<Rng>,
+    branch_limit: Option<u32>,
+    deadline: Option<Instant>,
+    aborted: bool,
 }
 
+/// Longer nogoods rarely fire again, so they are not worth a constraint.
+const MAX_NOGOOD_LEN: usize = 12;
+
 impl Engine {
     pub fn new() -> Self {
         Self {
             state: State::new(),
             constraints: Vec::new(),
+            all_diff_level: AllDiffLevel::Basic,
+            probing: false,
+            learning: false,
             watchers: vec![Vec::new(); NN],
             branches: 0,
+            decisions: Vec::new(),
+            root: None,
+            nogoods: 0,
+            rng: None,
+            branch_limit: None,
+            deadline: None,
+            aborted: false,
+        }
+    }
+
+    /// How hard `AllDifferent` constraints filter.
+    pub fn all_diff_level(&self) -> AllDiffLevel {
+        self.all_diff_level
+    }
+
+    /// Switch every posted `AllDifferent`, and those posted later, to `level`.
+    pub fn set_all_diff_level(&mut self, level: AllDiffLevel) {
+        self.all_diff_level = level;
+        for c in &mut self.constraints {
+            if let Constraint::AllDifferent { level: l, .. } = c {
+                *l = level;
+            }
         }
     }
 
-    pub fn add_constraint(&mut self, c: Constraint) {
+    /// Post a built-in constraint, or a boxed [`Propagator`] for a custom rule.
+    pub fn add_constraint(&mut self, c: impl Into<Constraint>) {
+        let c = c.into();
         let idx = self.constraints.len();
         for i in c.scope() {
             self.watchers[i as usize].push(idx);
@@ -30,57 +138,30 @@ impl Engine {
 
     pub fn enqueue_all(&mut self) {
         for i in 0..self.constraints.len() {
-            self.state.queue.push_back(i);
+            enqueue(&mut self.state, &self.constraints, i);
         }
     }
 
     pub fn enqueue_cell_constraints(&mut self, i: CellIx) {
         for &ci in &self.watchers[i as usize] {
-            self.state.queue.push_back(ci);
+            enqueue(&mut self.state, &self.constraints, ci);
         }
     }
 
     pub fn propagate(&mut self) -> Result<Solve, Contradiction> {
-        let mut any = false;
-        while let Some(ci) = self.state.queue.pop_front() {
-            let changed = self.constraints[ci].propagate(&mut self.state)?;
-            if changed {
-                any = true;
-                // Re-enqueue neighbors: every cell in this constraint
-                for j in self.constraints[ci].scope() {
-                    for &c2 in &self.watchers[j as usize] {
-                        self.state.queue.push_back(c2);
-                    }
-                }
-            }
-        }
-        if any {
-            Ok(Solve::Progress)
-        } else {
-            Ok(Solve::Stalled)
-        }
+        propagate_state(&mut self.state, &self.constraints, &self.watchers)
     }
+
     /// Initialize from givens: digits string of length 81 ('.' or '0' for blank).
     pub fn load_givens(&mut self, s: &str) -> Result<(), String> {
-        let bytes: Vec<u8> = s
-            .chars()
-            .filter(|ch| !ch.is_whitespace())
-            .map(|ch| ch as u8)
-            .collect();
-        if bytes.len() != NN {
-            return Err(format!("need 81 chars, got {}", bytes.len()));
-        }
-        for (i, &ch) in bytes.iter().enumerate() {
-            if ch == b'.' || ch == b'0' {
+        let digits = parse_givens(s)?;
+        for (i, &d) in digits.iter().enumerate() {
+            if d == 0 {
                 continue;
             }
-            if !(b'1'..=b'9').contains(&ch) {
-                return Err(format!("invalid char at {}: {}", i, ch as char));
-            }
-            let mask = bit_of_digit((ch - b'0') as u8);
             let i = i as CellIx;
             self.state
-                .assign(i, mask)
+                .assign(i, bit_of_digit(d))
                 .map_err(|_| "contradiction from givens".to_string())?;
             self.enqueue_cell_constraints(i);
         }
@@ -90,27 +171,244 @@ impl Engine {
         }
     }
 
+    /// Apply parity markers from a map string of length 81: 'E' (square) for
+    /// even, 'O' (circle) for odd, '.' or '0' for unmarked. The marked cells
+    /// are narrowed once at the root; no constraint is posted.
+    pub fn load_parity(&mut self, s: &str) -> Result<(), String> {
+        for (i, &ch) in grid_bytes(s)?.iter().enumerate() {
+            let cell = i as CellIx;
+            let mask = match ch.to_ascii_uppercase() {
+                b'.' | b'0' => continue,
+                b'E' => EVEN_MASK,
+                b'O' => ODD_MASK,
+                _ => return Err(format!("invalid char at {}: {}", i, ch as char)),
+            };
+            self.restrict(cell, mask)
+                .map_err(|_| "contradiction from parity markers".to_string())?;
+        }
+        match self.propagate() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("contradiction from parity markers".into()),
+        }
+    }
+
+    /// Narrow `cell` to `mask` for good, e.g. from a clue, and wake its
+    /// watchers.
+    fn restrict(&mut self, cell: CellIx, mask: Domain) -> Result<(), Contradiction> {
+        self.state.narrow(cell, mask)?;
+        self.enqueue_cell_constraints(cell);
+        Ok(())
+    }
+
+    /// Branches tried by [`Engine::search`] so far.
+    pub fn branches(&self) -> u32 {
+        self.branches
+    }
+
+    /// Nogoods learned by [`Engine::search`] so far.
+    pub fn nogoods(&self) -> usize {
+        self.nogoods
+    }
+
     pub fn solved(&self) -> bool {
         self.state.domains.iter().all(|&m| m.count_ones() == 1)
     }
 
     /// Choose MRV cell (domain size >1 with minimal count). Returns None if all singletons.
     pub fn choose_mrv(&self) -> Option<CellIx> {
+        choose_mrv(&self.state)
+    }
+
+    /// Singleton arc consistency: tentatively assign every candidate, and
+    /// drop the ones whose propagation ends in a contradiction. Repeats until
+    /// no candidate fails. Eliminations go on the trail, so an enclosing
+    /// search backtracks over them as usual. Returns whether anything was
+    /// eliminated.
+    pub fn probe(&mut self) -> Result<bool, Contradiction> {
+        let mut any = false;
+        loop {
+            let mut eliminated = false;
+            for i in 0..NN as CellIx {
+                let dom = self.state.domains[i as usize];
+                if dom.count_ones() < 2 {
+                    continue;
+                }
+                let mut m = dom;
+                while m != 0 {
+                    let d = m.trailing_zeros() as u8;
+                    let bit = bit_of_digit(d);
+                    m &= !bit;
+                    if self.state.domains[i as usize] & bit == 0 {
+                        continue;
+                    }
+
+                    let trail_len = self.state.trail.len();
+                    self.state.assign(i, bit)?;
+                    self.enqueue_cell_constraints(i);
+                    let failed = self.propagate().is_err();
+                    self.state.backtrack_to(trail_len);
+                    self.state.clear_queue();
+
+                    if failed {
+                        self.state.narrow(i, !bit)?;
+                        self.enqueue_cell_constraints(i);
+                        self.propagate()?;
+                        eliminated = true;
+                    }
+                }
+            }
+            if !eliminated {
+                return Ok(any);
+            }
+            any = true;
+        }
+    }
+
+    /// The current decisions lead nowhere. Drop every decision the conflict
+    /// does not need, then post the rest as a nogood so the combination is
+    /// pruned in other parts of the tree too. Branches that only failed
+    /// after a search below them are refuted through the nogoods learned
+    /// there; if those are missing the path is not worth learning.
+    fn learn_nogood(&mut self) {
+        if !self.learning || !self.refutes(&self.decisions) {
+            return;
+        }
+        let mut lits = self.decisions.clone();
+        let mut k = 0;
+        while k < lits.len() {
+            let lit = lits.remove(k);
+            if !self.refutes(&lits) {
+                lits.insert(k, lit);
+                k += 1;
+            }
+        }
+        if lits.len() > MAX_NOGOOD_LEN {
+            return;
+        }
+        let ci = self.constraints.len();
+        self.add_constraint(Constraint::Nogood { lits });
+        self.nogoods += 1;
+        enqueue(&mut self.state, &self.constraints, ci);
+    }
+
+    /// Whether propagation alone fails after making `lits` from the root.
+    fn refutes(&self, lits: &[(CellIx, u8)]) -> bool {
+        let Some(root) = &self.root else {
+            return false;
+        };
+        let mut st = root.clone();
+        for &(i, d) in lits {
+            if st.assign(i, bit_of_digit(d)).is_err() {
+                return true;
+            }
+            for &ci in &self.watchers[i as usize] {
+                enqueue(&mut st, &self.constraints, ci);
+            }
+        }
+        propagate_state(&mut st, &self.constraints, &self.watchers).is_err()
+    }
+
+    /// Like [`Engine::search`], but gives up at `deadline`. Returns `None`
+    /// if time ran out first.
+    pub fn search_until(&mut self, deadline: Instant) -> Result<Option<bool>, Contradiction> {
+        self.deadline = Some(deadline);
+        self.aborted = false;
+        let res = self.search();
+        self.deadline = None;
+        let timed_out = std::mem::take(&mut self.aborted);
+        match res {
+            Ok(false) if timed_out => Ok(None),
+            res => res.map(Some),
+        }
+    }
+
+    /// Put the engine back to `state` and drop constraints posted after the
+    /// first `constraints`, e.g. nogoods learned while solving one puzzle.
+    pub(crate) fn reset(&mut self, state: &State, constraints: usize) {
+        self.state.clone_from(state);
+        let dropped = self.constraints[constraints..]
+            .iter()
+            .filter(|c| matches!(c, Constraint::Nogood { .. }))
+            .count();
+        self.nogoods -= dropped;
+        self.constraints.truncate(constraints);
+        for w in &mut self.watchers {
+            w.retain(|&ci| ci < constraints);
+        }
+        self.decisions.clear();
+        self.root = None;
+        self.aborted = false;
+    }
+
+    /// MRV with ties broken by the search RNG.
+    fn choose_mrv_random(&mut self) -> Option<CellIx> {
         let mut best: Option<(CellIx, u32)> = None;
+        let mut ties = 0;
         for i in 0..NN {
-            let m = self.state.domains[i];
-            let cnt = m.count_ones();
-            if cnt > 1 {
-                match best {
-                    None => best = Some((i as CellIx, cnt)),
-                    Some((_, best_cnt)) if cnt < best_cnt => best = Some((i as CellIx, cnt)),
-                    _ => {}
+            let cnt = self.state.domains[i].count_ones();
+            if cnt < 2 {
+                continue;
+            }
+            match best {
+                Some((_, best_cnt)) if cnt > best_cnt => {}
+                Some((_, best_cnt)) if cnt == best_cnt => {
+                    // reservoir sampling over the tied cells
+                    ties += 1;
+                    if self.rng.as_mut().is_some_and(|rng| rng.below(ties) == 0) {
+                        best = Some((i as CellIx, cnt));
+                    }
+                }
+                _ => {
+                    best = Some((i as CellIx, cnt));
+                    ties = 1;
                 }
             }
         }
         best.map(|(i, _)| i)
     }
 
+    /// Randomized search that gives up on a run once its branch budget is
+    /// spent and starts over from the root with the next budget. Nogoods
+    /// learned with [`Engine::learning`] carry over between runs.
+    ///
+    /// Panics unless the budgets grow without bound (`base > 0`, and a
+    /// geometric factor above 1), which keeps the search complete.
+    pub fn search_with_restarts(&mut self, cfg: &RestartConfig) -> Result<bool, Contradiction> {
+        assert!(cfg.base > 0, "restart base must be positive");
+        if let RestartSchedule::Geometric(f) = cfg.schedule {
+            assert!(f > 1.0, "geometric restart factor must be above 1");
+        }
+        if self.state.trail.is_empty() && self.state.queue.is_empty() {
+            self.enqueue_all();
+        }
+        if self.propagate().is_err() {
+            return Ok(false);
+        }
+        let root = self.state.trail.len();
+        self.rng = Some(Rng::new(cfg.seed));
+
+        let mut run = 1;
+        let found = loop {
+            let budget = cfg.schedule.budget(run).saturating_mul(cfg.base).max(1);
+            self.branch_limit = Some(self.branches.saturating_add(budget));
+            self.aborted = false;
+            self.enqueue_all();
+            let found = self.search()?;
+            if found || !self.aborted {
+                break found;
+            }
+            self.state.backtrack_to(root);
+            self.state.clear_queue();
+            self.decisions.clear();
+            run += 1;
+        };
+
+        self.rng = None;
+        self.branch_limit = None;
+        self.aborted = false;
+        Ok(found)
+    }
+
     pub fn search(&mut self) -> Result<bool, Contradiction> {
         // enqueue all only at root
         if self.state.trail.is_empty() && self.state.queue.is_empty() {
@@ -138,12 +436,28 @@ impl Engine {
             return Ok(true);
         }
 
-        if self.state.domains.iter().any(|&m| m == 0) {
+        if self.state.domains.contains(&0) {
             return Ok(false);
         }
 
+        if self.probing {
+            match self.probe() {
+                Err(_) => return Ok(false),
+                Ok(_) if self.solved() => return Ok(true),
+                Ok(_) => {}
+            }
+        }
+
+        if self.learning && self.decisions.is_empty() {
+            self.root = Some(self.state.clone());
+        }
+
         // pick MRV cell
-        let i = match self.choose_mrv() {
+        let pick = match self.rng {
+            Some(_) => self.choose_mrv_random(),
+            None => self.choose_mrv(),
+        };
+        let i = match pick {
             None => {
                 return Ok(true);
             }
@@ -153,27 +467,41 @@ impl Engine {
 
         // branch over its values
         let trail_len = self.state.trail.len();
-        let mut m = dom;
-        while m != 0 {
-            let d = m.trailing_zeros() as u8;
+        let mut digits: Vec<u8> = (1..=9).filter(|&d| dom & bit_of_digit(d) != 0).collect();
+        if let Some(rng) = &mut self.rng {
+            rng.shuffle(&mut digits);
+        }
+        for d in digits {
             let bit = bit_of_digit(d);
-            m &= !bit;
             self.branches += 1;
+            if self.branch_limit.is_some_and(|limit| self.branches > limit)
+                || self.deadline.is_some_and(|t| Instant::now() >= t)
+            {
+                self.aborted = true;
+                return Ok(false);
+            }
             // try branch
             if self.state.assign(i, bit).is_ok() {
                 self.enqueue_cell_constraints(i);
+                self.decisions.push((i, d));
                 let res = self.search();
                 match res {
                     Ok(true) => {
                         return Ok(true);
                     }
+                    Ok(false) if self.aborted => {
+                        return Ok(false);
+                    }
                     Ok(false) => {
                         // branch failed, try next digit
+                        self.learn_nogood();
                     }
                     Err(Contradiction) => {
                         // branch failed, try next digit
+                        self.learn_nogood();
                     }
                 }
+                self.decisions.pop();
             }
             self.state.backtrack_to(trail_len);
         }
@@ -182,34 +510,414 @@ impl Engine {
     }
 }
 
+impl Engine {
+    /// Search on `threads` threads. Each thread runs its own depth-first
+    /// search on a cloned [`State`]; whenever a thread runs dry, a busy one
+    /// hands over the untried values of its current choice point. The first
+    /// solution found stops every thread and is copied into `self.state`.
+    ///
+    /// Branching is plain MRV: `probing`, `learning` and restarts only apply
+    /// to the sequential [`Engine::search`].
+    pub fn par_search(&mut self, threads: usize) -> Result<bool, Contradiction> {
+        let threads = threads.max(1);
+        let mut root = self.state.clone();
+        if root.trail.is_empty() && root.queue.is_empty() {
+            for ci in 0..self.constraints.len() {
+                enqueue(&mut root, &self.constraints, ci);
+            }
+        }
+
+        let pool = Pool {
+            work: Mutex::new(Work {
+                states: vec![root],
+                idle: 0,
+            }),
+            ready: Condvar::new(),
+            done: AtomicBool::new(false),
+            solution: Mutex::new(None),
+            branches: AtomicU32::new(0),
+        };
+        let this = &*self;
+        thread::scope(|s| {
+            for _ in 0..threads {
+                s.spawn(|| this.worker(&pool, threads));
+            }
+        });
+
+        self.branches += pool.branches.into_inner();
+        match pool.solution.into_inner().unwrap() {
+            Some(mut st) => {
+                st.clear_queue();
+                self.state = st;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn worker(&self, pool: &Pool, threads: usize) {
+        loop {
+            let mut st = {
+                let mut work = pool.work.lock().unwrap();
+                work.idle += 1;
+                loop {
+                    if pool.done.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    if let Some(st) = work.states.pop() {
+                        work.idle -= 1;
+                        break st;
+                    }
+                    if work.idle == threads {
+                        // nobody is left to produce work: the space is exhausted
+                        pool.done.store(true, Ordering::Relaxed);
+                        pool.ready.notify_all();
+                        return;
+                    }
+                    work = pool.ready.wait(work).unwrap();
+                }
+            };
+            self.par_dfs(&mut st, pool);
+        }
+    }
+
+    fn par_dfs(&self, st: &mut State, pool: &Pool) -> bool {
+        if pool.done.load(Ordering::Relaxed) {
+            return false;
+        }
+        if propagate_state(st, &self.constraints, &self.watchers).is_err() {
+            return false;
+        }
+        let i = match choose_mrv(st) {
+            None => {
+                pool.solution
+                    .lock()
+                    .unwrap()
+                    .get_or_insert_with(|| st.clone());
+                // under the lock, so no worker sits between its `done` check
+                // and `wait` and misses the wakeup
+                let _work = pool.work.lock().unwrap();
+                pool.done.store(true, Ordering::Relaxed);
+                pool.ready.notify_all();
+                return true;
+            }
+            Some(i) => i,
+        };
+
+        let trail_len = st.trail.len();
+        let mut m = st.domains[i as usize];
+        while m != 0 {
+            let d = m.trailing_zeros() as u8;
+            let bit = bit_of_digit(d);
+            m &= !bit;
+            if m != 0 && pool.wants_work() {
+                // donate the untried values of this choice point
+                let mut rest = st.clone();
+                rest.narrow(i, m).expect("untried values are in the domain");
+                for &ci in &self.watchers[i as usize] {
+                    enqueue(&mut rest, &self.constraints, ci);
+                }
+                pool.work.lock().unwrap().states.push(rest);
+                pool.ready.notify_one();
+                m = 0;
+            }
+
+            pool.branches.fetch_add(1, Ordering::Relaxed);
+            if st.assign(i, bit).is_ok() {
+                for &ci in &self.watchers[i as usize] {
+                    enqueue(st, &self.constraints, ci);
+                }
+                if self.par_dfs(st, pool) {
+                    return true;
+                }
+            }
+            st.backtrack_to(trail_len);
+            st.clear_queue();
+        }
+        false
+    }
+}
+
+/// The 81 cell characters of a grid string, whitespace skipped.
+fn grid_bytes(s: &str) -> Result<Vec<u8>, String> {
+    let bytes: Vec<u8> = s
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(|ch| ch as u8)
+        .collect();
+    if bytes.len() != NN {
+        return Err(format!("need 81 chars, got {}", bytes.len()));
+    }
+    Ok(bytes)
+}
+
+/// Digits of a givens string, 0 for blanks.
+pub(crate) fn parse_givens(s: &str) -> Result<[u8; NN], String> {
+    let bytes = grid_bytes(s)?;
+    let mut digits = [0; NN];
+    for (i, &ch) in bytes.iter().enumerate() {
+        if ch == b'.' || ch == b'0' {
+            continue;
+        }
+        if !(b'1'..=b'9').contains(&ch) {
+            return Err(format!("invalid char at {}: {}", i, ch as char));
+        }
+        digits[i] = ch - b'0';
+    }
+    Ok(digits)
+}
+
+/// Work shared between [`Engine::par_search`] threads.
+struct Pool {
+    work: Mutex<Work>,
+    ready: Condvar,
+    done: AtomicBool,
+    solution: Mutex<Option<State>>,
+    branches: AtomicU32,
+}
+
+struct Work {
+    states: Vec<State>,
+    /// Threads waiting for a state.
+    idle: usize,
+}
+
+impl Pool {
+    fn wants_work(&self) -> bool {
+        // don't block a busy thread on the lock just to ask
+        match self.work.try_lock() {
+            Ok(work) => work.idle > 0 && work.states.is_empty(),
+            Err(_) => false,
+        }
+    }
+}
+
+fn propagate_state(
+    st: &mut State,
+    constraints: &[Constraint],
+    watchers: &[Vec<usize>],
+) -> Result<Solve, Contradiction> {
+    let mut any = false;
+    while let Some(ci) = st.pop_queue() {
+        let trail_len = st.trail.len();
+        let changed = constraints[ci].propagate(st)?;
+        if changed {
+            any = true;
+            // Re-enqueue the watchers of the cells that actually changed
+            for t in trail_len..st.trail.len() {
+                let j = st.trail[t].0;
+                for &c2 in &watchers[j as usize] {
+                    enqueue(st, constraints, c2);
+                }
+            }
+        }
+    }
+    if any {
+        Ok(Solve::Progress)
+    } else {
+        Ok(Solve::Stalled)
+    }
+}
+
+fn choose_mrv(st: &State) -> Option<CellIx> {
+    let mut best: Option<(CellIx, u32)> = None;
+    for i in 0..NN {
+        let m = st.domains[i];
+        let cnt = m.count_ones();
+        if cnt > 1 {
+            match best {
+                None => best = Some((i as CellIx, cnt)),
+                Some((_, best_cnt)) if cnt < best_cnt => best = Some((i as CellIx, cnt)),
+                _ => {}
+            }
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// Queue constraint `ci`; prioritized ones go to the front.
+fn enqueue(st: &mut State, constraints: &[Constraint], ci: usize) {
+    st.push_queue(ci, constraints[ci].priority() > 0);
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn add_all_sudoku_constraints(e: &mut Engine) {
+    add_row_col_constraints(e);
+
+    for br in 0..3 {
+        for bc in 0..3 {
+            let mut cells = [0u8; 9];
+            let mut k = 0;
+            for dr in 0..3 {
+                for dc in 0..3 {
+                    cells[k] = idx(br * 3 + dr, bc * 3 + dc);
+                    k += 1;
+                }
+            }
+            e.add_constraint(Constraint::AllDifferent {
+                cells,
+                level: e.all_diff_level,
+            });
+        }
+    }
+}
+
+/// Jigsaw sudoku: rows, columns and the irregular regions from an 81-char
+/// region map (see [`parse_regions`]).
+pub fn add_jigsaw_constraints(e: &mut Engine, region_map: &str) -> Result<(), String> {
+    let regions = parse_regions(region_map)?;
+    add_row_col_constraints(e);
+    for r in 0..N as u8 {
+        let cells: [CellIx; 9] = region_cells(&regions, r)
+            .try_into()
+            .expect("parse_regions checks region sizes");
+        e.add_constraint(Constraint::AllDifferent {
+            cells,
+            level: e.all_diff_level,
+        });
+    }
+    Ok(())
+}
+
+fn add_row_col_constraints(e: &mut Engine) {
     for r in 0..N {
         let mut cells = [0u8; 9];
-        for c in 0..N {
-            cells[c] = idx(r, c);
+        for (c, cell) in cells.iter_mut().enumerate() {
+            *cell = idx(r, c);
         }
-        e.add_constraint(Constraint::AllDifferent { cells });
+        e.add_constraint(Constraint::AllDifferent {
+            cells,
+            level: e.all_diff_level,
+        });
     }
 
     for c in 0..N {
         let mut cells = [0u8; 9];
-        for r in 0..N {
-            cells[r] = idx(r, c);
+        for (r, cell) in cells.iter_mut().enumerate() {
+            *cell = idx(r, c);
         }
-        e.add_constraint(Constraint::AllDifferent { cells });
+        e.add_constraint(Constraint::AllDifferent {
+            cells,
+            level: e.all_diff_level,
+        });
     }
+}
 
-    for br in 0..3 {
-        for bc in 0..3 {
+/// Box/line intersection reasoning (pointing and claiming) for every pair of
+/// already-posted nine-cell houses that share two or more cells. Call it
+/// after posting the houses; it works the same for jigsaw regions and extra
+/// houses.
+pub fn add_box_line_constraints(e: &mut Engine) {
+    let houses: Vec<[CellIx; 9]> = e
+        .constraints
+        .iter()
+        .filter_map(|c| match c {
+            Constraint::AllDifferent { cells, .. } => Some(*cells),
+            _ => None,
+        })
+        .collect();
+    for (k, a) in houses.iter().enumerate() {
+        for b in &houses[k + 1..] {
+            let shared = a.iter().filter(|i| b.contains(i)).count();
+            if shared >= 2 {
+                e.add_constraint(Constraint::Intersection { a: *a, b: *b });
+            }
+        }
+    }
+}
+
+/// Extra nine-cell houses and whole-grid rules to post on top of the classic
+/// rows, columns and boxes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VariantSet {
+    pub diagonals: bool,
+    pub windoku: bool,
+    pub disjoint_groups: bool,
+    pub anti_knight: bool,
+    pub anti_king: bool,
+    pub non_consecutive: bool,
+}
+
+pub fn add_variant_constraints(e: &mut Engine, v: &VariantSet) {
+    if v.diagonals {
+        add_diagonal_constraints(e);
+    }
+    if v.windoku {
+        add_windoku_constraints(e);
+    }
+    if v.disjoint_groups {
+        add_disjoint_group_constraints(e);
+    }
+    if v.anti_knight {
+        add_anti_knight(e);
+    }
+    if v.anti_king {
+        add_anti_king(e);
+    }
+    if v.non_consecutive {
+        add_non_consecutive(e);
+    }
+}
+
+/// Both main diagonals (X-sudoku).
+pub fn add_diagonal_constraints(e: &mut Engine) {
+    let mut main = [0u8; 9];
+    let mut anti = [0u8; 9];
+    for k in 0..N {
+        main[k] = idx(k, k);
+        anti[k] = idx(k, N - 1 - k);
+    }
+    e.add_constraint(Constraint::AllDifferent {
+        cells: main,
+        level: e.all_diff_level,
+    });
+    e.add_constraint(Constraint::AllDifferent {
+        cells: anti,
+        level: e.all_diff_level,
+    });
+}
+
+/// The four windoku boxes at rows/cols 1..=3 and 5..=7.
+pub fn add_windoku_constraints(e: &mut Engine) {
+    for br in [1, 5] {
+        for bc in [1, 5] {
             let mut cells = [0u8; 9];
             let mut k = 0;
             for dr in 0..3 {
                 for dc in 0..3 {
+                    cells[k] = idx(br + dr, bc + dc);
+                    k += 1;
+                }
+            }
+            e.add_constraint(Constraint::AllDifferent {
+                cells,
+                level: e.all_diff_level,
+            });
+        }
+    }
+}
+
+/// Nine groups made of the cells at the same position in every box.
+pub fn add_disjoint_group_constraints(e: &mut Engine) {
+    for dr in 0..3 {
+        for dc in 0..3 {
+            let mut cells = [0u8; 9];
+            let mut k = 0;
+            for br in 0..3 {
+                for bc in 0..3 {
                     cells[k] = idx(br * 3 + dr, bc * 3 + dc);
                     k += 1;
                 }
             }
-            e.add_constraint(Constraint::AllDifferent { cells });
+            e.add_constraint(Constraint::AllDifferent {
+                cells,
+                level: e.all_diff_level,
+            });
         }
     }
 }
@@ -225,3 +933,280 @@ pub fn add_kropki_black(e: &mut Engine, a_rc: (usize, usize), b_rc: (usize, usiz
     let b = idx(b_rc.0, b_rc.1);
     e.add_constraint(Constraint::KropkiBlack { a, b });
 }
+
+/// Even digit at `rc`: narrows the cell once, no constraint is posted.
+pub fn add_even(e: &mut Engine, rc: (usize, usize)) -> Result<(), Contradiction> {
+    e.restrict(idx(rc.0, rc.1), EVEN_MASK)
+}
+
+/// Odd digit at `rc`, see [`add_even`].
+pub fn add_odd(e: &mut Engine, rc: (usize, usize)) -> Result<(), Contradiction> {
+    e.restrict(idx(rc.0, rc.1), ODD_MASK)
+}
+
+/// Digit at `a_rc` is less than the digit at `b_rc`.
+pub fn add_less(e: &mut Engine, a_rc: (usize, usize), b_rc: (usize, usize)) {
+    let a = idx(a_rc.0, a_rc.1);
+    let b = idx(b_rc.0, b_rc.1);
+    e.add_constraint(Constraint::Less { a, b });
+}
+
+/// Thermometer from bulb to tip, given as (row, col) pairs.
+pub fn add_thermo(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Thermo { cells });
+}
+
+/// Inequality (comparison) sudoku layout. `horizontal` has 9 rows of 8 signs
+/// between each cell and its right neighbour ('<', '>' or '.'); `vertical`
+/// has 8 rows of 9 signs between each cell and the one below it ('^' if the
+/// upper cell is smaller, 'v' if it is larger, '.' for none).
+pub fn add_inequalities(e: &mut Engine, horizontal: &str, vertical: &str) -> Result<(), String> {
+    let horizontal: Vec<char> = horizontal
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .collect();
+    let vertical: Vec<char> = vertical.chars().filter(|ch| !ch.is_whitespace()).collect();
+    if horizontal.len() != N * (N - 1) {
+        return Err(format!(
+            "need 72 horizontal signs, got {}",
+            horizontal.len()
+        ));
+    }
+    if vertical.len() != N * (N - 1) {
+        return Err(format!("need 72 vertical signs, got {}", vertical.len()));
+    }
+
+    for (k, &ch) in horizontal.iter().enumerate() {
+        let (r, c) = (k / (N - 1), k % (N - 1));
+        match ch {
+            '.' => {}
+            '<' => add_less(e, (r, c), (r, c + 1)),
+            '>' => add_less(e, (r, c + 1), (r, c)),
+            _ => return Err(format!("invalid horizontal sign at {}: {}", k, ch)),
+        }
+    }
+    for (k, &ch) in vertical.iter().enumerate() {
+        let (r, c) = (k / N, k % N);
+        match ch {
+            '.' => {}
+            '^' => add_less(e, (r, c), (r + 1, c)),
+            'v' | 'V' => add_less(e, (r + 1, c), (r, c)),
+            _ => return Err(format!("invalid vertical sign at {}: {}", k, ch)),
+        }
+    }
+    Ok(())
+}
+
+/// Quadruple clue on the 2x2 block whose top-left cell is `top_left_rc`.
+pub fn add_quadruple(e: &mut Engine, top_left_rc: (usize, usize), digits: &[u8]) {
+    let (r, c) = top_left_rc;
+    assert!(
+        r < N - 1 && c < N - 1,
+        "quadruple block must fit in the grid"
+    );
+    assert!(digits.len() <= 4, "quadruple takes at most 4 digits");
+    assert!(
+        digits.iter().all(|d| (1..=9).contains(d)),
+        "quadruple digits must be 1-9"
+    );
+    let cells = [idx(r, c), idx(r, c + 1), idx(r + 1, c), idx(r + 1, c + 1)];
+    e.add_constraint(Constraint::Quadruple {
+        cells,
+        digits: digits.to_vec(),
+    });
+}
+
+/// Line that reads the same from both ends, given as (row, col) pairs.
+pub fn add_palindrome(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Palindrome { cells });
+}
+
+/// Clone of the region `rcs` shifted by `offset` rows and columns; paired
+/// cells hold the same digit.
+pub fn add_clone(e: &mut Engine, rcs: &[(usize, usize)], offset: (isize, isize)) {
+    let a = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    let b = rcs
+        .iter()
+        .map(|&(r, c)| {
+            let r = r as isize + offset.0;
+            let c = c as isize + offset.1;
+            assert!(
+                (0..N as isize).contains(&r) && (0..N as isize).contains(&c),
+                "clone region leaves the grid"
+            );
+            idx(r as usize, c as usize)
+        })
+        .collect();
+    e.add_constraint(Constraint::Clone { a, b });
+}
+
+/// Between line along (row, col) pairs; the first and last are the circles.
+pub fn add_between(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells: Vec<CellIx> = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    assert!(cells.len() >= 2, "between line needs two ends");
+    e.add_constraint(Constraint::Between {
+        end_a: cells[0],
+        end_b: cells[cells.len() - 1],
+        middle: cells[1..cells.len() - 1].to_vec(),
+    });
+}
+
+/// Lockout line along (row, col) pairs; the first and last are the diamonds.
+pub fn add_lockout(e: &mut Engine, rcs: &[(usize, usize)], min_diff: u8) {
+    let cells: Vec<CellIx> = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    assert!(cells.len() >= 2, "lockout line needs two ends");
+    e.add_constraint(Constraint::Lockout {
+        end_a: cells[0],
+        end_b: cells[cells.len() - 1],
+        middle: cells[1..cells.len() - 1].to_vec(),
+        min_diff,
+    });
+}
+
+/// Region sum line along (row, col) pairs; every box segment sums the same.
+pub fn add_region_sum(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::RegionSum { cells });
+}
+
+/// Entropic line along (row, col) pairs: every three adjacent cells hold one
+/// low (1-3), one middle (4-6) and one high (7-9) digit.
+pub fn add_entropic(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Entropic { cells });
+}
+
+/// Modular line along (row, col) pairs: every three adjacent cells hold one
+/// digit of each residue mod 3.
+pub fn add_modular(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Modular { cells });
+}
+
+/// Zipper line along (row, col) pairs.
+pub fn add_zipper(e: &mut Engine, rcs: &[(usize, usize)]) {
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Zipper { cells });
+}
+
+/// Side of the grid an outside clue sits on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Row (Left/Right) or column (Top/Bottom) `index`, ordered starting from
+/// the clue.
+fn house_from(side: Side, index: usize) -> [CellIx; 9] {
+    let mut cells = [0u8; 9];
+    for (k, cell) in cells.iter_mut().enumerate() {
+        *cell = match side {
+            Side::Left => idx(index, k),
+            Side::Right => idx(index, N - 1 - k),
+            Side::Top => idx(k, index),
+            Side::Bottom => idx(N - 1 - k, index),
+        };
+    }
+    cells
+}
+
+pub fn add_x_sum(e: &mut Engine, side: Side, index: usize, sum: u8) {
+    let cells = house_from(side, index);
+    e.add_constraint(Constraint::XSum { cells, sum });
+}
+
+pub fn add_skyscraper(e: &mut Engine, side: Side, index: usize, visible: u8) {
+    let cells = house_from(side, index);
+    e.add_constraint(Constraint::Skyscraper { cells, visible });
+}
+
+/// Row indexing: in every row, the digit in column `c` (0-based) names the
+/// column that holds the digit `c + 1`.
+pub fn add_row_indexing(e: &mut Engine, cols: &[usize]) {
+    for r in 0..N {
+        let mut house = [0u8; 9];
+        for (c, cell) in house.iter_mut().enumerate() {
+            *cell = idx(r, c);
+        }
+        for &c in cols {
+            e.add_constraint(Constraint::Index {
+                index_cell: idx(r, c),
+                house,
+                digit: (c + 1) as u8,
+            });
+        }
+    }
+}
+
+/// Column indexing: in every column, the digit in row `r` (0-based) names
+/// the row that holds the digit `r + 1`.
+pub fn add_col_indexing(e: &mut Engine, rows: &[usize]) {
+    for c in 0..N {
+        let mut house = [0u8; 9];
+        for (r, cell) in house.iter_mut().enumerate() {
+            *cell = idx(r, c);
+        }
+        for &r in rows {
+            e.add_constraint(Constraint::Index {
+                index_cell: idx(r, c),
+                house,
+                digit: (r + 1) as u8,
+            });
+        }
+    }
+}
+
+/// The 159 rule: row indexing on columns 1, 5 and 9.
+pub fn add_159(e: &mut Engine) {
+    add_row_indexing(e, &[0, 4, 8]);
+}
+
+/// Only the listed digit tuples are allowed on the cells at `rcs`, in order.
+pub fn add_table(e: &mut Engine, rcs: &[(usize, usize)], tuples: Vec<Vec<u8>>) {
+    assert!(
+        tuples.iter().all(|t| t.len() == rcs.len()),
+        "table tuples must match the number of cells"
+    );
+    assert!(
+        tuples.iter().flatten().all(|d| (1..=9).contains(d)),
+        "table digits must be 1-9"
+    );
+    let cells = rcs.iter().map(|&(r, c)| idx(r, c)).collect();
+    e.add_constraint(Constraint::Table { cells, tuples });
+}
+
+/// Little killer clue: the diagonal starting at `start_rc` and stepping by
+/// `dir` (each of -1 or 1) until it leaves the grid sums to `sum`.
+pub fn add_little_killer(e: &mut Engine, start_rc: (usize, usize), dir: (isize, isize), sum: u8) {
+    let mut cells = Vec::new();
+    let (mut r, mut c) = (start_rc.0 as isize, start_rc.1 as isize);
+    while (0..N as isize).contains(&r) && (0..N as isize).contains(&c) {
+        cells.push(idx(r as usize, c as usize));
+        r += dir.0;
+        c += dir.1;
+    }
+    e.add_constraint(Constraint::LittleKiller { cells, sum });
+}
+
+pub fn add_anti_knight(e: &mut Engine) {
+    for cell in 0..NN as CellIx {
+        e.add_constraint(Constraint::AntiKnight { cell });
+    }
+}
+
+pub fn add_anti_king(e: &mut Engine) {
+    for cell in 0..NN as CellIx {
+        e.add_constraint(Constraint::AntiKing { cell });
+    }
+}
+
+pub fn add_non_consecutive(e: &mut Engine) {
+    for cell in 0..NN as CellIx {
+        e.add_constraint(Constraint::NonConsecutive { cell });
+    }
+}
@@ -1,20 +1,44 @@
 #![allow(clippy::redundant_pub_crate)]
+mod batch;
 mod constraints;
 mod engine;
+mod logic;
+mod regions;
+mod rng;
 mod state;
 mod types;
 
-pub use constraints::Constraint;
-pub use engine::{Engine, add_all_sudoku_constraints, add_kropki_black, add_kropki_white};
+pub use batch::{BatchReport, Outcome, PuzzleResult, solve_batch};
+pub use constraints::{AllDiffLevel, Constraint, Propagator};
+pub use engine::{
+    Engine, RestartConfig, RestartSchedule, Side, VariantSet, add_159, add_all_sudoku_constraints,
+    add_anti_king, add_anti_knight, add_between, add_box_line_constraints, add_clone,
+    add_col_indexing, add_diagonal_constraints, add_disjoint_group_constraints, add_entropic,
+    add_even, add_inequalities, add_jigsaw_constraints, add_kropki_black, add_kropki_white,
+    add_less, add_little_killer, add_lockout, add_modular, add_non_consecutive, add_odd,
+    add_palindrome, add_quadruple, add_region_sum, add_row_indexing, add_skyscraper, add_table,
+    add_thermo, add_variant_constraints, add_windoku_constraints, add_x_sum, add_zipper,
+};
+pub use logic::{LogicReport, Technique, solve_logically};
+pub use regions::{parse_regions, region_cells};
 pub use state::State;
 pub use types::{
-    CellIx, Contradiction, DIGITS_MASK, Domain, EVEN_MASK, N, NN, Solve, box_of, col_of, row_of,
+    CellIx, Contradiction, DIGITS_MASK, Domain, EVEN_MASK, HIGH_MASK, LOW_MASK, MID_MASK, N, NN,
+    ODD_MASK, Solve, box_of, col_of, row_of,
 };
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Stalls early, so solving it takes a real search.
+    pub(crate) const HARD: &str =
+        "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..";
+
+    /// [`HARD`] with a wrong 2 at r1c2; only search finds out.
+    pub(crate) const HARD_UNSAT: &str =
+        "82.........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..";
+
     #[test]
     fn solves_classic() {
         let p = "2...7.1.3.7..8..5.3....6.....6......91..5..28......5.....3....4.2..9..7.5.4.1...6";
@@ -25,6 +49,459 @@ mod tests {
         assert!(eng.solved());
     }
 
+    #[test]
+    fn fills_empty_grid_with_extra_houses() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        add_variant_constraints(
+            &mut eng,
+            &VariantSet {
+                diagonals: true,
+                windoku: true,
+                disjoint_groups: true,
+                ..Default::default()
+            },
+        );
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+
+        let diag: Domain = (0..N).fold(0, |acc, k| acc | eng.state.domains[k * N + k]);
+        assert_eq!(diag, DIGITS_MASK);
+        let window: Domain = (1..4)
+            .flat_map(|r| (5..8).map(move |c| (r, c)))
+            .fold(0, |acc, (r, c)| acc | eng.state.domains[r * N + c]);
+        assert_eq!(window, DIGITS_MASK);
+    }
+
+    #[test]
+    fn solves_jigsaw() {
+        let regions = "111422366\
+                       144442336\
+                       114222366\
+                       114552336\
+                       144552236\
+                       777599936\
+                       777555936\
+                       788589999\
+                       778888889";
+        let p = "6.3.4.8.5.2.9.8.6.8.7.6.9.1.5.4.9.3.2.4.9.1.3.6.7.1.4.9.8.5.4.6.3.8.6.9.3.9.2.6.7";
+        let mut eng = Engine::new();
+        add_jigsaw_constraints(&mut eng, regions).unwrap();
+        eng.load_givens(p).unwrap();
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+
+        let parsed = parse_regions(regions).unwrap();
+        for r in 0..N as u8 {
+            let seen: Domain = region_cells(&parsed, r)
+                .iter()
+                .fold(0, |acc, &i| acc | eng.state.domains[i as usize]);
+            assert_eq!(seen, DIGITS_MASK);
+        }
+    }
+
+    #[test]
+    fn parity_map_narrows_and_solves() {
+        let parity = "EO.......\
+                      .........\
+                      .........\
+                      .........\
+                      ....E....\
+                      .........\
+                      .........\
+                      .........\
+                      ........O";
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        let n = eng.constraints.len();
+        eng.load_parity(parity).unwrap();
+        assert_eq!(eng.constraints.len(), n);
+        assert_eq!(eng.state.domains[0], EVEN_MASK);
+        assert_eq!(eng.state.domains[1], ODD_MASK);
+        assert_eq!(eng.state.domains[40], EVEN_MASK);
+        assert_eq!(eng.state.domains[80], ODD_MASK);
+
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+        assert_eq!(eng.state.domains[0] & ODD_MASK, 0);
+        assert_eq!(eng.state.domains[80] & EVEN_MASK, 0);
+
+        assert!(Engine::new().load_parity(&"X".repeat(81)).is_err());
+    }
+
+    #[test]
+    fn parity_helpers_narrow_once() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        let n = eng.constraints.len();
+        add_even(&mut eng, (0, 0)).unwrap();
+        add_odd(&mut eng, (8, 8)).unwrap();
+        assert_eq!(eng.constraints.len(), n);
+        assert_eq!(eng.state.domains[0], EVEN_MASK);
+        assert_eq!(eng.state.domains[80], ODD_MASK);
+        assert!(add_odd(&mut eng, (0, 0)).is_err());
+    }
+
+    #[test]
+    fn inequality_layout_posts_less_constraints() {
+        let horizontal = "<.......\
+                          ........\
+                          ........\
+                          ........\
+                          ........\
+                          ........\
+                          ........\
+                          ........\
+                          .......>";
+        let vertical = "^........\
+                        .........\
+                        .........\
+                        .........\
+                        .........\
+                        .........\
+                        .........\
+                        ........v";
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        add_inequalities(&mut eng, horizontal, vertical).unwrap();
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+
+        let d = |i: usize| eng.state.domains[i].trailing_zeros();
+        assert!(d(0) < d(1));
+        assert!(d(0) < d(9));
+        assert!(d(79) > d(80));
+        assert!(d(71) > d(80));
+
+        assert!(add_inequalities(&mut Engine::new(), "<", vertical).is_err());
+    }
+
+    #[test]
+    fn outside_clues_hold_in_solution() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        add_skyscraper(&mut eng, Side::Left, 0, 9);
+        add_x_sum(&mut eng, Side::Bottom, 8, 10);
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+
+        let digit = |i: usize| eng.state.domains[i].trailing_zeros() as usize;
+        for c in 0..N {
+            assert_eq!(digit(c), c + 1);
+        }
+        // r8c8 is X; the X cells going up column 8 sum to 10
+        let x = digit(80);
+        let total: usize = (0..x).map(|k| digit((N - 1 - k) * N + 8)).sum();
+        assert_eq!(total, 10);
+    }
+
+    #[test]
+    fn indexing_holds_in_solution() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        add_159(&mut eng);
+        add_col_indexing(&mut eng, &[0]);
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+
+        let digit = |r: usize, c: usize| eng.state.domains[r * N + c].trailing_zeros() as usize;
+        for r in 0..N {
+            for c in [0, 4, 8] {
+                assert_eq!(digit(r, digit(r, c) - 1), c + 1);
+            }
+        }
+        for c in 0..N {
+            assert_eq!(digit(digit(0, c) - 1, c), 1);
+        }
+    }
+
+    /// r0c0 + r0c1 = 10, written outside the crate's own constraint set.
+    struct PairSum {
+        a: CellIx,
+        b: CellIx,
+    }
+
+    impl Propagator for PairSum {
+        fn scope<'a>(&'a self) -> Box<dyn Iterator<Item = CellIx> + 'a> {
+            Box::new([self.a, self.b].into_iter())
+        }
+
+        fn propagate(&self, state: &mut State) -> Result<bool, Contradiction> {
+            // d + (10 - d): mirror the other cell's digits around 5
+            let mirror = |m: Domain| {
+                (1..=9)
+                    .filter(|&d| m & (1 << d) != 0)
+                    .fold(0, |acc, d| acc | 1 << (10 - d))
+            };
+            let da = state.domains[self.a as usize];
+            let db = state.domains[self.b as usize];
+            let changed_a = state.narrow(self.a, mirror(db))?;
+            let changed_b = state.narrow(self.b, mirror(da))?;
+            Ok(changed_a || changed_b)
+        }
+
+        fn name(&self) -> &str {
+            "pair-sum"
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "quadruple digits must be 1-9")]
+    fn quadruple_rejects_out_of_range_digit() {
+        add_quadruple(&mut Engine::new(), (0, 0), &[1, 10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "quadruple block must fit")]
+    fn quadruple_rejects_block_off_the_grid() {
+        add_quadruple(&mut Engine::new(), (8, 0), &[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "table digits must be 1-9")]
+    fn table_rejects_out_of_range_digit() {
+        add_table(&mut Engine::new(), &[(0, 0), (0, 1)], vec![vec![1, 0]]);
+    }
+
+    #[test]
+    fn solves_with_custom_propagator() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        eng.add_constraint(Box::new(PairSum { a: 0, b: 1 }));
+        assert_eq!(eng.constraints.last().unwrap().name(), "pair-sum");
+        eng.load_givens(&format!("3{}", ".".repeat(80))).unwrap();
+        assert_eq!(eng.state.domains[1], 1 << 7);
+
+        assert!(eng.search().unwrap());
+        assert!(eng.solved());
+    }
+
+    #[test]
+    fn matching_all_diff_removes_naked_pair() {
+        let run = |level: AllDiffLevel, before_posting: bool| {
+            let mut eng = Engine::new();
+            if before_posting {
+                eng.set_all_diff_level(level);
+            }
+            add_all_sudoku_constraints(&mut eng);
+            if !before_posting {
+                eng.set_all_diff_level(level);
+            }
+            // r0c0 and r0c1 both {1,2}
+            for i in [0, 1] {
+                eng.state.narrow(i, 0b110).unwrap();
+                eng.enqueue_cell_constraints(i);
+            }
+            eng.propagate().unwrap();
+            eng.state.domains
+        };
+        let basic = run(AllDiffLevel::Basic, true);
+        let strong = run(AllDiffLevel::Matching, true);
+        assert_eq!(run(AllDiffLevel::Matching, false), strong);
+        assert_eq!(basic[5] & 0b110, 0b110);
+        for (c, &m) in strong.iter().enumerate().take(9).skip(2) {
+            assert_eq!(m & 0b110, 0, "r0c{c} kept the pair digits");
+        }
+        // r3c0 only sees one of the pair
+        assert_eq!(strong[27] & 0b110, 0b110);
+    }
+
+    #[test]
+    fn box_line_removes_pointing_digit() {
+        let run = |box_line: bool| {
+            let mut eng = Engine::new();
+            add_all_sudoku_constraints(&mut eng);
+            if box_line {
+                add_box_line_constraints(&mut eng);
+                // 9 boxes x (3 rows + 3 columns)
+                assert_eq!(eng.constraints.len(), 27 + 54);
+            }
+            // 3 leaves box 0 everywhere but row 0
+            for r in 1..3 {
+                for c in 0..3 {
+                    let i = types::idx(r, c);
+                    eng.state.narrow(i, !(1 << 3)).unwrap();
+                    eng.enqueue_cell_constraints(i);
+                }
+            }
+            eng.propagate().unwrap();
+            eng.state.domains
+        };
+        let plain = run(false);
+        let boxed = run(true);
+        assert_ne!(plain[5] & (1 << 3), 0);
+        for (c, &m) in boxed.iter().enumerate().take(9).skip(3) {
+            assert_eq!(m & (1 << 3), 0, "r0c{c} kept 3");
+        }
+        // the pointing row keeps it inside the box, other rows everywhere
+        assert_ne!(boxed[0] & (1 << 3), 0);
+        assert_ne!(boxed[9 + 5] & (1 << 3), 0);
+    }
+
+    #[test]
+    fn probing_removes_failing_candidates() {
+        // stalls with singles, probing alone finishes it
+        let p = "....14....3....2...7..........9...3.6.1.............8.2.....1.4....5.6.....7.8...";
+        let mut plain = Engine::new();
+        add_all_sudoku_constraints(&mut plain);
+        plain.load_givens(p).unwrap();
+        assert!(!plain.solved());
+
+        let mut probed = Engine::new();
+        add_all_sudoku_constraints(&mut probed);
+        probed.load_givens(p).unwrap();
+        assert!(probed.probe().unwrap());
+        assert!(probed.solved());
+        assert_eq!(probed.branches(), 0);
+
+        // the eliminations are sound: search finds the same grid
+        assert!(plain.search().unwrap());
+        assert_eq!(plain.state.domains, probed.state.domains);
+    }
+
+    #[test]
+    fn learning_posts_reduced_sound_nogoods() {
+        let mut eng = Engine::new();
+        eng.learning = true;
+        add_all_sudoku_constraints(&mut eng);
+        eng.load_givens(HARD).unwrap();
+        assert!(eng.search().unwrap());
+
+        let learned: Vec<&[(CellIx, u8)]> = eng
+            .constraints
+            .iter()
+            .filter_map(|c| match c {
+                Constraint::Nogood { lits } => Some(&lits[..]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(learned.len(), eng.nogoods());
+        for lits in &learned {
+            // the solution never makes every literal true
+            assert!(
+                !lits
+                    .iter()
+                    .all(|&(i, d)| eng.state.domains[i as usize] == 1 << d),
+                "{lits:?} excludes the solution"
+            );
+        }
+        // reduced down to single eliminations, not whole decision paths
+        assert!(learned.iter().any(|lits| lits.len() == 1));
+    }
+
+    #[test]
+    fn restarts_are_reproducible_per_seed() {
+        let sample = |seed: u64| {
+            let mut eng = Engine::new();
+            add_all_sudoku_constraints(&mut eng);
+            let cfg = RestartConfig {
+                seed,
+                ..Default::default()
+            };
+            assert!(eng.search_with_restarts(&cfg).unwrap());
+            assert!(eng.solved());
+            eng.state.domains
+        };
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+    }
+
+    #[test]
+    fn restarts_report_unsat() {
+        for schedule in [RestartSchedule::Luby, RestartSchedule::Geometric(1.5)] {
+            let mut eng = Engine::new();
+            add_all_sudoku_constraints(&mut eng);
+            eng.load_givens(HARD_UNSAT).unwrap();
+            let cfg = RestartConfig {
+                seed: 1,
+                schedule,
+                base: 1,
+            };
+            assert!(!eng.search_with_restarts(&cfg).unwrap());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "geometric restart factor")]
+    fn restarts_reject_flat_schedule() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        let cfg = RestartConfig {
+            schedule: RestartSchedule::Geometric(1.0),
+            ..Default::default()
+        };
+        let _ = eng.search_with_restarts(&cfg);
+    }
+
+    #[test]
+    fn restarts_solve_hard_puzzle() {
+        let p = HARD;
+
+        let mut plain = Engine::new();
+        add_all_sudoku_constraints(&mut plain);
+        plain.load_givens(p).unwrap();
+        assert!(plain.search().unwrap());
+
+        for schedule in [RestartSchedule::Luby, RestartSchedule::Geometric(1.5)] {
+            let mut eng = Engine::new();
+            eng.learning = true;
+            add_all_sudoku_constraints(&mut eng);
+            eng.load_givens(p).unwrap();
+            let cfg = RestartConfig {
+                seed: 3,
+                schedule,
+                base: 10,
+            };
+            assert!(eng.search_with_restarts(&cfg).unwrap());
+            assert_eq!(eng.state.domains, plain.state.domains);
+        }
+    }
+
+    #[test]
+    fn parallel_search_matches_sequential() {
+        let p = HARD;
+
+        let mut seq = Engine::new();
+        add_all_sudoku_constraints(&mut seq);
+        seq.load_givens(p).unwrap();
+        assert!(seq.search().unwrap());
+
+        for threads in [1, 4] {
+            let mut par = Engine::new();
+            add_all_sudoku_constraints(&mut par);
+            par.load_givens(p).unwrap();
+            assert!(par.par_search(threads).unwrap());
+            assert!(par.solved());
+            assert_eq!(par.state.domains, seq.state.domains);
+            assert!(par.branches() > 0);
+        }
+    }
+
+    #[test]
+    fn parallel_search_with_idle_threads_terminates() {
+        // nearly solved grids leave most threads without work
+        let solution =
+            "812753649943682175675491283154237896369845721287169534521974368438526917796318452";
+        for blank in 0..81 {
+            let mut p = solution.as_bytes().to_vec();
+            p[blank] = b'.';
+            p[(blank * 7 + 3) % 81] = b'.';
+            let mut eng = Engine::new();
+            add_all_sudoku_constraints(&mut eng);
+            eng.load_givens(std::str::from_utf8(&p).unwrap()).unwrap();
+            assert!(eng.par_search(8).unwrap());
+            assert!(eng.solved());
+        }
+    }
+
+    #[test]
+    fn parallel_search_reports_unsat() {
+        let mut eng = Engine::new();
+        add_all_sudoku_constraints(&mut eng);
+        eng.load_givens(HARD_UNSAT).unwrap();
+        assert!(!eng.par_search(4).unwrap());
+    }
+
     #[test]
     fn solves_kropki_white_only() {
         let p = "...7....4.1.........6......4...........3.7...........8......7.........8.3....2...";
@@ -48,6 +525,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn solves_kropki() {
         let p = ".......57...............................................................57.......";
         let mut eng = Engine::new();
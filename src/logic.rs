@@ -0,0 +1,579 @@
+use crate::{
+    CellIx, Contradiction, Domain, N, NN, State,
+    constraints::{propagate_all_diff_matching, propagate_intersection},
+    types::{box_of, col_of, idx, row_of},
+};
+
+/// Deduction steps of the logical solver, easiest first. The derived order
+/// doubles as the difficulty order used for grading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    NakedSingle,
+    HiddenSingle,
+    BoxLine,
+    Subset,
+    XWing,
+    XYWing,
+    XYZWing,
+    Swordfish,
+    SimpleColoring,
+    Jellyfish,
+}
+
+impl Technique {
+    pub const ALL: [Technique; 10] = [
+        Technique::NakedSingle,
+        Technique::HiddenSingle,
+        Technique::BoxLine,
+        Technique::Subset,
+        Technique::XWing,
+        Technique::XYWing,
+        Technique::XYZWing,
+        Technique::Swordfish,
+        Technique::SimpleColoring,
+        Technique::Jellyfish,
+    ];
+
+    fn apply(self, st: &mut State) -> Result<bool, Contradiction> {
+        match self {
+            Technique::NakedSingle => naked_singles(st),
+            Technique::HiddenSingle => hidden_singles(st),
+            Technique::BoxLine => box_line(st),
+            Technique::Subset => subsets(st),
+            Technique::XWing => fish(st, 2),
+            Technique::Swordfish => fish(st, 3),
+            Technique::Jellyfish => fish(st, 4),
+            Technique::XYWing => xy_wing(st),
+            Technique::XYZWing => xyz_wing(st),
+            Technique::SimpleColoring => simple_coloring(st),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogicReport {
+    /// Techniques in the order they made progress.
+    pub steps: Vec<Technique>,
+    pub solved: bool,
+}
+
+impl LogicReport {
+    /// Hardest technique the solve needed, the usual grading measure.
+    pub fn hardest(&self) -> Option<Technique> {
+        self.steps.iter().copied().max()
+    }
+}
+
+/// Solve a classic grid with deductions only: always retry the easiest
+/// technique after any progress, stop when solved or when nothing applies.
+pub fn solve_logically(st: &mut State) -> Result<LogicReport, Contradiction> {
+    let mut report = LogicReport::default();
+    loop {
+        if st.domains.iter().all(|&m| m.count_ones() == 1) {
+            report.solved = true;
+            return Ok(report);
+        }
+        match step(st)? {
+            Some(t) => report.steps.push(t),
+            None => return Ok(report),
+        }
+    }
+}
+
+/// Apply the easiest technique that makes progress.
+pub fn step(st: &mut State) -> Result<Option<Technique>, Contradiction> {
+    for t in Technique::ALL {
+        if t.apply(st)? {
+            return Ok(Some(t));
+        }
+    }
+    Ok(None)
+}
+
+fn houses() -> [[CellIx; 9]; 27] {
+    let mut hs = [[0u8; 9]; 27];
+    for r in 0..N {
+        for c in 0..N {
+            let i = idx(r, c);
+            hs[r][c] = i;
+            hs[N + c][r] = i;
+            hs[2 * N + box_of(i)][(r % 3) * 3 + c % 3] = i;
+        }
+    }
+    hs
+}
+
+fn sees(a: CellIx, b: CellIx) -> bool {
+    a != b && (row_of(a) == row_of(b) || col_of(a) == col_of(b) || box_of(a) == box_of(b))
+}
+
+fn naked_singles(st: &mut State) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for i in 0..NN as CellIx {
+        let di = st.domains[i as usize];
+        if di.count_ones() != 1 {
+            continue;
+        }
+        for j in 0..NN as CellIx {
+            if sees(i, j) && st.narrow(j, !di)? {
+                changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn hidden_singles(st: &mut State) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for house in houses() {
+        for d in 1..=9u8 {
+            let bit = 1u16 << d;
+            let mut places = house.iter().filter(|&&i| st.domains[i as usize] & bit != 0);
+            let (Some(&i), None) = (places.next(), places.next()) else {
+                continue;
+            };
+            if st.assign(i, bit)? {
+                changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn box_line(st: &mut State) -> Result<bool, Contradiction> {
+    let hs = houses();
+    let mut changed = false;
+    for b in &hs[2 * N..] {
+        for line in &hs[..2 * N] {
+            if line.iter().filter(|i| b.contains(i)).count() == 3 {
+                let pointing = propagate_intersection(st, b, line)?;
+                let claiming = propagate_intersection(st, line, b)?;
+                changed |= pointing || claiming;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn subsets(st: &mut State) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for house in houses() {
+        if propagate_all_diff_matching(st, &house)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Basic fish of the given size, with rows as base sets and then columns.
+/// `size` lines where the digit fits only in the same `size` cover lines
+/// clear it from the rest of those cover lines.
+fn fish(st: &mut State, size: usize) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for d in 1..=9u8 {
+        let bit = 1u16 << d;
+        for by_rows in [true, false] {
+            let cell = |base: usize, cover: usize| {
+                if by_rows {
+                    idx(base, cover)
+                } else {
+                    idx(cover, base)
+                }
+            };
+            // cover positions of the digit per base line, as a 9-bit mask
+            let mut pos = [0u16; 9];
+            for (base, p) in pos.iter_mut().enumerate() {
+                for cover in 0..N {
+                    if st.domains[cell(base, cover) as usize] & bit != 0 {
+                        *p |= 1 << cover;
+                    }
+                }
+            }
+            let candidates: Vec<usize> = (0..N)
+                .filter(|&b| (2..=size as u32).contains(&pos[b].count_ones()))
+                .collect();
+            for combo in combinations(&candidates, size) {
+                let covers = combo.iter().fold(0u16, |acc, &b| acc | pos[b]);
+                if covers.count_ones() as usize != size {
+                    continue;
+                }
+                for base in (0..N).filter(|b| !combo.contains(b)) {
+                    for cover in (0..N).filter(|&c| covers & (1 << c) != 0) {
+                        if st.narrow(cell(base, cover), !bit)? {
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn combinations(items: &[usize], k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    for (n, &first) in items.iter().enumerate() {
+        for mut rest in combinations(&items[n + 1..], k - 1) {
+            rest.insert(0, first);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+/// Pivot {x,y} seeing pincers {x,z} and {y,z}: whatever the pivot is, one
+/// pincer is z, so cells seeing both pincers lose z.
+fn xy_wing(st: &mut State) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    let bivalue: Vec<CellIx> = (0..NN as CellIx)
+        .filter(|&i| st.domains[i as usize].count_ones() == 2)
+        .collect();
+    for &pivot in &bivalue {
+        let dp = st.domains[pivot as usize];
+        for &p1 in &bivalue {
+            for &p2 in &bivalue {
+                if p1 >= p2 || !sees(pivot, p1) || !sees(pivot, p2) {
+                    continue;
+                }
+                let (d1, d2) = (st.domains[p1 as usize], st.domains[p2 as usize]);
+                let z = d1 & d2;
+                let wing_ok = z.count_ones() == 1
+                    && (d1 | d2) & !z == dp
+                    && d1 & dp != 0
+                    && d2 & dp != 0
+                    && dp & z == 0;
+                if wing_ok && eliminate_seen_by_all(st, &[p1, p2], z)? {
+                    changed = true;
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// Pivot {x,y,z} seeing pincers {x,z} and {y,z}: z is in one of the three,
+/// so cells seeing all of them lose z.
+fn xyz_wing(st: &mut State) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for pivot in 0..NN as CellIx {
+        let dp = st.domains[pivot as usize];
+        if dp.count_ones() != 3 {
+            continue;
+        }
+        let pincers: Vec<CellIx> = (0..NN as CellIx)
+            .filter(|&j| {
+                let dj = st.domains[j as usize];
+                sees(pivot, j) && dj.count_ones() == 2 && dj & !dp == 0
+            })
+            .collect();
+        for (k, &p1) in pincers.iter().enumerate() {
+            for &p2 in &pincers[k + 1..] {
+                let (d1, d2) = (st.domains[p1 as usize], st.domains[p2 as usize]);
+                let z = d1 & d2;
+                if z.count_ones() == 1
+                    && d1 != d2
+                    && eliminate_seen_by_all(st, &[pivot, p1, p2], z)?
+                {
+                    changed = true;
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn eliminate_seen_by_all(
+    st: &mut State,
+    cells: &[CellIx],
+    bit: Domain,
+) -> Result<bool, Contradiction> {
+    let mut changed = false;
+    for j in 0..NN as CellIx {
+        if cells.iter().all(|&c| sees(c, j)) && st.narrow(j, !bit)? {
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Simple coloring on conjugate pairs of one digit. Two same-colored cells in
+/// a house make that color false; an uncolored cell seeing both colors can't
+/// hold the digit.
+fn simple_coloring(st: &mut State) -> Result<bool, Contradiction> {
+    let hs = houses();
+    let mut changed = false;
+    for d in 1..=9u8 {
+        let bit = 1u16 << d;
+        let mut links: Vec<Vec<CellIx>> = vec![Vec::new(); NN];
+        for house in &hs {
+            let places: Vec<CellIx> = house
+                .iter()
+                .copied()
+                .filter(|&i| st.domains[i as usize] & bit != 0)
+                .collect();
+            if let [a, b] = places[..] {
+                links[a as usize].push(b);
+                links[b as usize].push(a);
+            }
+        }
+
+        let mut colored = [false; NN];
+        for start in 0..NN {
+            if links[start].is_empty() || colored[start] {
+                continue;
+            }
+            // (cell, color) for one connected chain
+            let mut chain = Vec::new();
+            let mut stack = vec![(start as CellIx, true)];
+            colored[start] = true;
+            while let Some((i, c)) = stack.pop() {
+                chain.push((i, c));
+                for &j in &links[i as usize] {
+                    if !colored[j as usize] {
+                        colored[j as usize] = true;
+                        stack.push((j, !c));
+                    }
+                }
+            }
+
+            // color wrap
+            for side in [true, false] {
+                let same: Vec<CellIx> = chain
+                    .iter()
+                    .filter(|&&(_, c)| c == side)
+                    .map(|&(i, _)| i)
+                    .collect();
+                let clash = same.iter().any(|&a| same.iter().any(|&b| sees(a, b)));
+                if clash {
+                    for &i in &same {
+                        if st.narrow(i, !bit)? {
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if changed {
+                return Ok(true);
+            }
+
+            // color trap
+            for j in 0..NN as CellIx {
+                if st.domains[j as usize] & bit == 0 || chain.iter().any(|&(i, _)| i == j) {
+                    continue;
+                }
+                let sees_on = chain.iter().any(|&(i, c)| c && sees(i, j));
+                let sees_off = chain.iter().any(|&(i, c)| !c && sees(i, j));
+                if sees_on && sees_off && st.narrow(j, !bit)? {
+                    changed = true;
+                }
+            }
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DIGITS_MASK;
+    use crate::types::bit_of_digit;
+
+    fn mask(digits: &[u8]) -> Domain {
+        digits.iter().fold(0, |acc, &d| acc | bit_of_digit(d))
+    }
+
+    /// Givens only, no propagation: the solver does all the work.
+    fn state_from(givens: &str) -> State {
+        let mut st = State::new();
+        for (i, ch) in givens.bytes().enumerate() {
+            if ch.is_ascii_digit() && ch != b'0' {
+                st.assign(i as CellIx, bit_of_digit(ch - b'0')).unwrap();
+            }
+        }
+        st
+    }
+
+    /// Leave digit `d` as a candidate only in `keep`, given as (row, col).
+    fn only_in(st: &mut State, d: u8, keep: &[(usize, usize)]) {
+        for r in 0..N {
+            for c in 0..N {
+                if !keep.contains(&(r, c)) {
+                    st.domains[idx(r, c) as usize] &= !mask(&[d]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_x_wing_clears_cover_columns() {
+        let mut st = State::new();
+        // 5 only in columns 1 and 7 on rows 0 and 4
+        for r in [0, 4] {
+            for c in 0..N {
+                if c != 1 && c != 7 {
+                    st.domains[idx(r, c) as usize] &= !mask(&[5]);
+                }
+            }
+        }
+        assert!(fish(&mut st, 2).unwrap());
+        for r in (0..N).filter(|&r| r != 0 && r != 4) {
+            assert_eq!(st.domains[idx(r, 1) as usize] & mask(&[5]), 0);
+            assert_eq!(st.domains[idx(r, 7) as usize] & mask(&[5]), 0);
+        }
+        assert_eq!(st.domains[idx(0, 1) as usize], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_swordfish_clears_cover_columns() {
+        let mut st = State::new();
+        // 5 on rows 0, 3 and 6 only in columns 1, 4 and 7, two per row
+        for (r, cols) in [(0, [1, 4]), (3, [4, 7]), (6, [1, 7])] {
+            for c in (0..N).filter(|c| !cols.contains(c)) {
+                st.domains[idx(r, c) as usize] &= !mask(&[5]);
+            }
+        }
+        let before = st.clone();
+        assert!(!fish(&mut st, 2).unwrap());
+
+        assert!(fish(&mut st, 3).unwrap());
+        for r in (0..N).filter(|&r| r % 3 != 0) {
+            for c in [1, 4, 7] {
+                assert_eq!(st.domains[idx(r, c) as usize] & mask(&[5]), 0);
+            }
+            assert_ne!(st.domains[idx(r, 0) as usize] & mask(&[5]), 0);
+        }
+        assert_eq!(
+            st.domains[idx(0, 1) as usize],
+            before.domains[idx(0, 1) as usize]
+        );
+    }
+
+    #[test]
+    fn test_jellyfish_clears_cover_columns() {
+        let mut st = State::new();
+        // 7 on rows 0, 2, 4 and 6 in a cycle over columns 0, 2, 4 and 6
+        for (r, cols) in [(0, [0, 2]), (2, [2, 4]), (4, [4, 6]), (6, [6, 0])] {
+            for c in (0..N).filter(|c| !cols.contains(c)) {
+                st.domains[idx(r, c) as usize] &= !mask(&[7]);
+            }
+        }
+        assert!(!fish(&mut st, 2).unwrap());
+        assert!(!fish(&mut st, 3).unwrap());
+
+        assert!(fish(&mut st, 4).unwrap());
+        for r in [1, 3, 5, 7, 8] {
+            for c in [0, 2, 4, 6] {
+                assert_eq!(st.domains[idx(r, c) as usize] & mask(&[7]), 0);
+            }
+            assert_ne!(st.domains[idx(r, 1) as usize] & mask(&[7]), 0);
+        }
+    }
+
+    #[test]
+    fn test_xyz_wing_eliminates_z() {
+        let mut st = State::new();
+        // pivot r0c0 {1,2,3}, pincers r0c4 {1,3} in its row and r1c1 {2,3}
+        // in its box
+        st.domains[idx(0, 0) as usize] = mask(&[1, 2, 3]);
+        st.domains[idx(0, 4) as usize] = mask(&[1, 3]);
+        st.domains[idx(1, 1) as usize] = mask(&[2, 3]);
+
+        assert!(!xy_wing(&mut st).unwrap());
+        assert!(xyz_wing(&mut st).unwrap());
+        // only r0c1 and r0c2 see all three
+        assert_eq!(st.domains[idx(0, 1) as usize], DIGITS_MASK & !mask(&[3]));
+        assert_eq!(st.domains[idx(0, 2) as usize], DIGITS_MASK & !mask(&[3]));
+        assert_eq!(st.domains[idx(0, 3) as usize], DIGITS_MASK);
+        assert_eq!(st.domains[idx(1, 0) as usize], DIGITS_MASK);
+    }
+
+    #[test]
+    fn test_simple_coloring_wrap() {
+        let mut st = State::new();
+        // chain r0c0 - r0c4 - r4c4 - r4c1 - r1c1 over row, col, row, col;
+        // r2c2 keeps box 0 from linking the ends, which share a color and
+        // see each other there
+        only_in(
+            &mut st,
+            4,
+            &[(0, 0), (0, 4), (4, 4), (4, 1), (1, 1), (2, 2)],
+        );
+
+        assert!(simple_coloring(&mut st).unwrap());
+        for (r, c) in [(0, 0), (4, 4), (1, 1)] {
+            assert_eq!(st.domains[idx(r, c) as usize] & mask(&[4]), 0);
+        }
+        for (r, c) in [(0, 4), (4, 1), (2, 2)] {
+            assert_ne!(st.domains[idx(r, c) as usize] & mask(&[4]), 0);
+        }
+    }
+
+    #[test]
+    fn test_simple_coloring_trap() {
+        let mut st = State::new();
+        // chain r0c0 - r4c0 - r4c4 - r0c4 over col, row, col; r0c7 sees
+        // r0c0 and r0c4, which have opposite colors
+        only_in(&mut st, 4, &[(0, 0), (4, 0), (4, 4), (0, 4), (0, 7)]);
+
+        assert!(simple_coloring(&mut st).unwrap());
+        assert_eq!(st.domains[idx(0, 7) as usize] & mask(&[4]), 0);
+        for (r, c) in [(0, 0), (4, 0), (4, 4), (0, 4)] {
+            assert_ne!(st.domains[idx(r, c) as usize] & mask(&[4]), 0);
+        }
+    }
+
+    #[test]
+    fn test_xy_wing_eliminates_z() {
+        let mut st = State::new();
+        // pivot r0c0 {1,2}, pincers r0c5 {1,3} and r4c0 {2,3}
+        st.domains[idx(0, 0) as usize] = mask(&[1, 2]);
+        st.domains[idx(0, 5) as usize] = mask(&[1, 3]);
+        st.domains[idx(4, 0) as usize] = mask(&[2, 3]);
+
+        assert!(xy_wing(&mut st).unwrap());
+        // r4c5 sees both pincers
+        assert_eq!(st.domains[idx(4, 5) as usize] & mask(&[3]), 0);
+        assert_ne!(st.domains[idx(5, 5) as usize] & mask(&[3]), 0);
+    }
+
+    #[test]
+    fn test_solves_and_grades_classic() {
+        let p = "2...7.1.3.7..8..5.3....6.....6......91..5..28......5.....3....4.2..9..7.5.4.1...6";
+        let mut st = state_from(p);
+        let report = solve_logically(&mut st).unwrap();
+        assert!(report.solved);
+        assert!(report.hardest() >= Some(Technique::HiddenSingle));
+    }
+
+    #[test]
+    fn test_grades_puzzle_needing_advanced_technique() {
+        let p = "....14....3....2...7..........9...3.6.1.............8.2.....1.4....5.6.....7.8...";
+        let mut st = state_from(p);
+        let report = solve_logically(&mut st).unwrap();
+        assert!(report.solved);
+        assert!(report.hardest() > Some(Technique::Subset));
+    }
+
+    #[test]
+    fn test_unsolved_puzzle_keeps_solution_candidates() {
+        let p = crate::tests::HARD;
+        let mut eng = crate::Engine::new();
+        crate::add_all_sudoku_constraints(&mut eng);
+        eng.load_givens(p).unwrap();
+        assert!(eng.search().unwrap());
+
+        let mut st = state_from(p);
+        solve_logically(&mut st).unwrap();
+        for i in 0..NN {
+            assert_ne!(
+                st.domains[i] & eng.state.domains[i],
+                0,
+                "cell {} lost its digit",
+                i
+            );
+        }
+    }
+}
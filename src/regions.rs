@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+use crate::{
+    CellIx, N, NN,
+    types::{col_of, idx, row_of},
+};
+
+/// Parse an 81-char region map (e.g. `111222333...`) into a region index
+/// 0..9 per cell. Any non-whitespace char works as a label; each of the nine
+/// labels must cover exactly nine orthogonally connected cells.
+pub fn parse_regions(s: &str) -> Result<[u8; NN], String> {
+    let chars: Vec<char> = s.chars().filter(|ch| !ch.is_whitespace()).collect();
+    if chars.len() != NN {
+        return Err(format!("need 81 chars, got {}", chars.len()));
+    }
+
+    // labels numbered in order of first appearance
+    let mut labels: Vec<char> = Vec::with_capacity(N);
+    let mut regions = [0u8; NN];
+    for (i, &ch) in chars.iter().enumerate() {
+        let r = match labels.iter().position(|&l| l == ch) {
+            Some(r) => r,
+            None => {
+                if labels.len() == N {
+                    return Err(format!(
+                        "more than 9 regions, extra label {:?} at {}",
+                        ch, i
+                    ));
+                }
+                labels.push(ch);
+                labels.len() - 1
+            }
+        };
+        regions[i] = r as u8;
+    }
+    if labels.len() != N {
+        return Err(format!("need 9 regions, got {}", labels.len()));
+    }
+
+    for (r, &label) in labels.iter().enumerate() {
+        let cells = region_cells(&regions, r as u8);
+        if cells.len() != N {
+            return Err(format!(
+                "region {:?} has {} cells, need 9",
+                label,
+                cells.len()
+            ));
+        }
+        if !is_connected(&regions, &cells) {
+            return Err(format!("region {:?} is not connected", label));
+        }
+    }
+
+    Ok(regions)
+}
+
+pub fn region_cells(regions: &[u8; NN], r: u8) -> Vec<CellIx> {
+    (0..NN as CellIx)
+        .filter(|&i| regions[i as usize] == r)
+        .collect()
+}
+
+fn is_connected(regions: &[u8; NN], cells: &[CellIx]) -> bool {
+    let Some(&start) = cells.first() else {
+        return false;
+    };
+    let r = regions[start as usize];
+    let mut seen = [false; NN];
+    let mut queue = VecDeque::from([start]);
+    seen[start as usize] = true;
+    let mut reached = 0;
+    while let Some(i) = queue.pop_front() {
+        reached += 1;
+        let (row, col) = (row_of(i), col_of(i));
+        let mut neighbours = Vec::with_capacity(4);
+        if row > 0 {
+            neighbours.push(idx(row - 1, col));
+        }
+        if row + 1 < N {
+            neighbours.push(idx(row + 1, col));
+        }
+        if col > 0 {
+            neighbours.push(idx(row, col - 1));
+        }
+        if col + 1 < N {
+            neighbours.push(idx(row, col + 1));
+        }
+        for j in neighbours {
+            if regions[j as usize] == r && !seen[j as usize] {
+                seen[j as usize] = true;
+                queue.push_back(j);
+            }
+        }
+    }
+    reached == cells.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOXES: &str =
+        "111222333111222333111222333444555666444555666444555666777888999777888999777888999";
+
+    #[test]
+    fn parses_standard_boxes() {
+        let regions = parse_regions(BOXES).unwrap();
+        assert_eq!(regions[0], 0);
+        assert_eq!(regions[80], 8);
+        assert_eq!(
+            region_cells(&regions, 4),
+            vec![30, 31, 32, 39, 40, 41, 48, 49, 50]
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_region_size() {
+        let mut s: Vec<char> = BOXES.chars().collect();
+        s[3] = '1';
+        let s: String = s.into_iter().collect();
+        assert!(parse_regions(&s).is_err());
+    }
+
+    #[test]
+    fn rejects_disconnected_region() {
+        // swap one cell of region 1 with one of region 9
+        let mut s: Vec<char> = BOXES.chars().collect();
+        s[0] = '9';
+        s[80] = '1';
+        let s: String = s.into_iter().collect();
+        assert!(parse_regions(&s).unwrap_err().contains("not connected"));
+    }
+}
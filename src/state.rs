@@ -7,6 +7,8 @@ pub struct State {
     pub domains: [Domain; NN],
     pub(crate) trail: Vec<(CellIx, Domain)>,
     pub(crate) queue: VecDeque<usize>,
+    /// Constraints currently in `queue`, so each is queued at most once.
+    pub(crate) queued: Vec<bool>,
 }
 
 impl State {
@@ -15,6 +17,7 @@ impl State {
             domains: [DIGITS_MASK; NN],
             trail: Vec::with_capacity(256),
             queue: VecDeque::new(),
+            queued: Vec::new(),
         }
     }
 
@@ -38,6 +41,32 @@ impl State {
         self.narrow(i, single)
     }
 
+    /// Queue constraint `ci` unless it is already waiting.
+    pub(crate) fn push_queue(&mut self, ci: usize, front: bool) {
+        if ci >= self.queued.len() {
+            self.queued.resize(ci + 1, false);
+        }
+        if self.queued[ci] {
+            return;
+        }
+        self.queued[ci] = true;
+        if front {
+            self.queue.push_front(ci);
+        } else {
+            self.queue.push_back(ci);
+        }
+    }
+
+    pub(crate) fn pop_queue(&mut self) -> Option<usize> {
+        let ci = self.queue.pop_front()?;
+        self.queued[ci] = false;
+        Some(ci)
+    }
+
+    pub(crate) fn clear_queue(&mut self) {
+        while self.pop_queue().is_some() {}
+    }
+
     pub fn backtrack_to(&mut self, trail_len: usize) {
         while self.trail.len() > trail_len {
             let (i, old) = self.trail.pop().unwrap();
@@ -52,6 +81,12 @@ impl State {
     }
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,6 +113,26 @@ mod tests {
         assert!(st.queue.is_empty());
     }
 
+    #[test]
+    fn queue_holds_each_constraint_once() {
+        let mut st = State::new();
+        st.push_queue(3, false);
+        st.push_queue(1, false);
+        st.push_queue(3, false);
+        st.push_queue(2, true);
+        st.push_queue(1, true);
+        assert_eq!(st.queue, [2, 3, 1]);
+
+        assert_eq!(st.pop_queue(), Some(2));
+        st.push_queue(2, false);
+        assert_eq!(st.queue, [3, 1, 2]);
+
+        st.clear_queue();
+        assert!(st.queue.is_empty());
+        st.push_queue(3, false);
+        assert_eq!(st.queue, [3]);
+    }
+
     #[test]
     fn narrow_reduced_domain_and_records_trail() {
         let mut st = State::new();
@@ -4,8 +4,13 @@ pub const NN: usize = N * N; //81
 pub type CellIx = u8; //0..80
 pub type Domain = u16; //bits 1..=9 used
 
-pub const DIGITS_MASK: Domain = 0b_11_1111_1110;
+pub const DIGITS_MASK: Domain = 0b11_1111_1110;
 pub const EVEN_MASK: Domain = (1 << 2) | (1 << 4) | (1 << 6) | (1 << 8);
+pub const ODD_MASK: Domain = DIGITS_MASK & !EVEN_MASK;
+
+pub const LOW_MASK: Domain = (1 << 1) | (1 << 2) | (1 << 3);
+pub const MID_MASK: Domain = (1 << 4) | (1 << 5) | (1 << 6);
+pub const HIGH_MASK: Domain = (1 << 7) | (1 << 8) | (1 << 9);
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Solve {
@@ -39,6 +44,27 @@ pub fn bit_of_digit(d: u8) -> Domain {
     1u16 << d
 }
 
+#[inline]
+pub fn min_digit(m: Domain) -> u8 {
+    m.trailing_zeros() as u8
+}
+
+#[inline]
+pub fn max_digit(m: Domain) -> u8 {
+    15 - m.leading_zeros() as u8
+}
+
+/// Mask of digits lo..=hi, clipped to 1..=9. Empty if lo > hi.
+#[inline]
+pub fn range_mask(lo: i32, hi: i32) -> Domain {
+    let lo = lo.max(1);
+    let hi = hi.min(9);
+    if lo > hi {
+        return 0;
+    }
+    ((1u16 << (hi + 1)) - 1) & !((1u16 << lo) - 1)
+}
+
 #[inline]
 pub fn _digit_of_bit(bit: Domain) -> Option<u8> {
     if bit == 0 || !bit.is_power_of_two() {
@@ -57,4 +83,14 @@ mod tests {
         let res = bit_of_digit(4);
         assert_eq!(res, 0b1_0000);
     }
+
+    #[test]
+    fn test_min_max_and_range() {
+        let m = bit_of_digit(3) | bit_of_digit(7);
+        assert_eq!(min_digit(m), 3);
+        assert_eq!(max_digit(m), 7);
+        assert_eq!(range_mask(1, 9), DIGITS_MASK);
+        assert_eq!(range_mask(4, 5), bit_of_digit(4) | bit_of_digit(5));
+        assert_eq!(range_mask(6, 5), 0);
+    }
 }